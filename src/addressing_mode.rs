@@ -15,3 +15,108 @@ pub enum AddressingMode {
     Indirect_X,
     Indirect_Y,
 }
+
+impl AddressingMode {
+    pub const ALL: [Self; 13] = [
+        Self::Implicit,
+        Self::Accumulator,
+        Self::Immediate,
+        Self::ZeroPage,
+        Self::ZeroPage_X,
+        Self::ZeroPage_Y,
+        Self::Relative,
+        Self::Absolute,
+        Self::Absolute_X,
+        Self::Absolute_Y,
+        Self::Indirect,
+        Self::Indirect_X,
+        Self::Indirect_Y,
+    ];
+
+    /// Canonical name, e.g. `ZeroPage,X` or `(Indirect),Y`.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Implicit => "Implicit",
+            Self::Accumulator => "Accumulator",
+            Self::Immediate => "Immediate",
+            Self::ZeroPage => "ZeroPage",
+            Self::ZeroPage_X => "ZeroPage,X",
+            Self::ZeroPage_Y => "ZeroPage,Y",
+            Self::Relative => "Relative",
+            Self::Absolute => "Absolute",
+            Self::Absolute_X => "Absolute,X",
+            Self::Absolute_Y => "Absolute,Y",
+            Self::Indirect => "(Indirect)",
+            Self::Indirect_X => "(Indirect,X)",
+            Self::Indirect_Y => "(Indirect),Y",
+        }
+    }
+
+    /// Variant name as spelled in code, e.g. `ZeroPage_X`.
+    const fn variant_name(self) -> &'static str {
+        match self {
+            Self::ZeroPage_X => "ZeroPage_X",
+            Self::ZeroPage_Y => "ZeroPage_Y",
+            Self::Absolute_X => "Absolute_X",
+            Self::Absolute_Y => "Absolute_Y",
+            Self::Indirect => "Indirect",
+            Self::Indirect_X => "Indirect_X",
+            Self::Indirect_Y => "Indirect_Y",
+            _ => self.name(),
+        }
+    }
+}
+
+impl std::fmt::Display for AddressingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseAddressingModeError(String);
+
+impl std::fmt::Display for ParseAddressingModeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown addressing mode '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseAddressingModeError {}
+
+impl std::str::FromStr for AddressingMode {
+    type Err = ParseAddressingModeError;
+
+    /// Accepts the canonical name (`ZeroPage,X`) or the variant name (`ZeroPage_X`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name() == s || mode.variant_name() == s)
+            .ok_or_else(|| ParseAddressingModeError(s.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_round_trips() {
+        for mode in AddressingMode::ALL {
+            assert_eq!(mode.to_string().parse(), Ok(mode));
+        }
+    }
+
+    #[test]
+    fn test_parse_variant_names() {
+        assert_eq!("ZeroPage_X".parse(), Ok(AddressingMode::ZeroPage_X));
+        assert_eq!("Indirect_Y".parse(), Ok(AddressingMode::Indirect_Y));
+        assert_eq!("Indirect".parse(), Ok(AddressingMode::Indirect));
+        assert_eq!(
+            "Zeropage".parse::<AddressingMode>(),
+            Err(ParseAddressingModeError("Zeropage".to_owned()))
+        );
+    }
+}
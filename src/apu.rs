@@ -0,0 +1,27 @@
+/// Number of APU registers, `[0x4000..=0x4017]` less the OAM DMA and joypad ports in between.
+const REGISTERS: usize = 0x18;
+
+/// Register file of the 2A03's audio unit. Nothing is synthesized yet, the registers only latch
+/// what the CPU writes so the bus has somewhere to send them.
+#[derive(Debug, Default)]
+pub struct Apu {
+    registers: [u8; REGISTERS],
+}
+
+impl Apu {
+    /// Write to the register at `addr`, one of `0x4000..=0x4013`, `0x4015` or `0x4017`.
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        self.registers[usize::from(addr - 0x4000)] = data;
+    }
+
+    #[must_use]
+    pub fn register(&self, addr: u16) -> u8 {
+        self.registers[usize::from(addr - 0x4000)]
+    }
+
+    /// `0x4015` read. Without length counters the channel bits mirror the enable bits last written.
+    #[must_use]
+    pub fn read_status(&self) -> u8 {
+        self.register(0x4015) & 0b0001_1111
+    }
+}
@@ -1,4 +1,4 @@
-use nes_emulator::cpu::{memory::Memory, CPU};
+use nes_emulator::cpu::{memory::Memory, BrkPolicy, CPU};
 use rand::{distributions::Uniform, prelude::Distribution};
 use sdl2::{
     event::Event,
@@ -8,7 +8,6 @@ use sdl2::{
 };
 
 /// Read user input and write it to mem[0xFF]
-
 #[rustfmt::skip]
 fn handle_user_input(cpu: &mut CPU, event_pump: &mut EventPump) {
     for event in event_pump.poll_iter() {
@@ -114,7 +113,8 @@ fn main() {
     ];
 
     let mut cpu = CPU::default();
-    cpu.load(&game_code);
+    cpu.set_brk_policy(BrkPolicy::Halt);
+    cpu.load(&game_code).unwrap();
     cpu.reset();
 
     let mut screen_state = [0_u8; 32 * 3 * 32];
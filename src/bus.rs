@@ -1,60 +1,463 @@
-use crate::cpu::memory::Memory;
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+};
+
+use crate::{apu::Apu, cartridge::Rom, cpu::memory::Memory, game_genie::GameGenie, joypad::Joypad};
 
 const RAM: u16 = 0x0000;
 const RAM_MIRRORS_END: u16 = 0x1FFF;
 const PPU_REGISTERS: u16 = 0x2000;
 const PPU_REGISTERS_MIRRORS_END: u16 = 0x3FFF;
+const APU_REGISTERS: u16 = 0x4000;
+const APU_REGISTERS_END: u16 = 0x4013;
+const OAM_DMA: u16 = 0x4014;
+const APU_STATUS: u16 = 0x4015;
+const JOYPAD_1: u16 = 0x4016;
+// Reads come from the second controller, writes go to the APU frame counter.
+const JOYPAD_2: u16 = 0x4017;
+/// Controller reads only drive the low bits, the rest is left over on the bus.
+const JOYPAD_OPEN_BUS_BITS: u8 = 0b1110_0000;
+const PRG_ROM: u16 = 0x8000;
+const PRG_ROM_END: u16 = 0xFFFF;
+const RESET_VECTOR: u16 = 0xFFFC;
+const RESET_VECTOR_END: u16 = 0xFFFD;
+const RAM_ADDR_BITS: u16 = 0b0000_0111_1111_1111;
+const PPU_ADDR_BITS: u16 = 0b0010_0000_0000_0111;
 const VRAM: usize = 2048; // 2^11
+const RAM_UNMIRRORED: usize = RAM_MIRRORS_END as usize + 1; // 8 KiB
+
+/// Access to an address nothing on the bus answers, recorded in strict mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnmappedAccess {
+    Read { addr: u16 },
+    Write { addr: u16, data: u8 },
+}
+
+impl fmt::Display for UnmappedAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read { addr } => write!(f, "read from unmapped address {addr:#06x}"),
+            Self::Write { addr, data } => {
+                write!(f, "write of {data:#04x} to unmapped address {addr:#06x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for UnmappedAccess {}
+
+#[derive(Debug)]
 pub struct Bus {
-    cpu_vram: [u8; VRAM],
+    // Sized for the unmirrored view; only the first 2 KiB are used while mirroring.
+    cpu_vram: [u8; RAM_UNMIRRORED],
+    mirror_ram: bool,
+    /// Last value driven on the data bus, what an unmapped read sees.
+    data_latch: Cell<u8>,
+    strict: bool,
+    unmapped: RefCell<Vec<UnmappedAccess>>,
+    game_genie: Vec<GameGenie>,
+    apu: Apu,
+    joypad1: Joypad,
+    joypad2: Joypad,
+    /// Page of the last OAM DMA; there is no PPU to copy it into yet.
+    oam_dma_page: Option<u8>,
+    rom: Option<Rom>,
+    /// Set by [`Memory::set_reset_vector`], shadows whatever ROM has at 0xFFFC.
+    reset_vector: Option<u16>,
 }
 
 impl Default for Bus {
     fn default() -> Self {
         Self {
-            cpu_vram: [0; VRAM],
+            cpu_vram: [0; RAM_UNMIRRORED],
+            mirror_ram: true,
+            data_latch: Cell::new(0),
+            strict: false,
+            unmapped: RefCell::default(),
+            game_genie: Vec::new(),
+            apu: Apu::default(),
+            joypad1: Joypad::default(),
+            joypad2: Joypad::default(),
+            oam_dma_page: None,
+            rom: None,
+            reset_vector: None,
         }
     }
 }
 
-impl Memory for Bus {
-    fn mem_read(&self, addr: u16) -> u8 {
-        const RAM_ADDR_BITS: u16 = 0b0000_0111_1111_1111;
-        const PPU_ADDR_BITS: u16 = 0b0010_0000_0000_0111;
+impl Bus {
+    /// A bus with `rom`'s PRG-ROM mapped at `[0x8000..=0xFFFF]`.
+    #[must_use]
+    pub fn new(rom: Rom) -> Self {
+        Self {
+            rom: Some(rom),
+            ..Self::default()
+        }
+    }
 
-        match addr {
-            RAM..=RAM_MIRRORS_END => {
-                let mirror_down_addr = addr & RAM_ADDR_BITS;
-                self.cpu_vram[mirror_down_addr as usize]
-            }
+    /// With mirroring off `[0x0000..0x2000]` is a flat 8 KiB, handy when debugging one address.
+    pub const fn set_mirror_ram(&mut self, mirror_ram: bool) {
+        self.mirror_ram = mirror_ram;
+    }
+
+    #[must_use]
+    pub const fn mirror_ram(&self) -> bool {
+        self.mirror_ram
+    }
 
-            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => {
-                let mirror_down_addr = addr & PPU_ADDR_BITS;
-                todo!()
+    /// In strict mode unmapped accesses are recorded for [`Bus::take_unmapped_accesses`]; either
+    /// way reads return open bus and writes are dropped.
+    pub const fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    #[must_use]
+    pub const fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Unmapped accesses recorded since the last call, oldest first.
+    pub fn take_unmapped_accesses(&mut self) -> Vec<UnmappedAccess> {
+        std::mem::take(self.unmapped.get_mut())
+    }
+
+    /// Patch reads at the code's address, like a Game Genie sitting between cartridge and console.
+    pub fn add_game_genie(&mut self, code: GameGenie) {
+        self.game_genie.push(code);
+    }
+
+    pub fn clear_game_genie(&mut self) {
+        self.game_genie.clear();
+    }
+
+    #[must_use]
+    pub const fn joypad1(&self) -> &Joypad {
+        &self.joypad1
+    }
+
+    #[must_use]
+    pub const fn joypad2(&self) -> &Joypad {
+        &self.joypad2
+    }
+
+    pub const fn joypad1_mut(&mut self) -> &mut Joypad {
+        &mut self.joypad1
+    }
+
+    pub const fn joypad2_mut(&mut self) -> &mut Joypad {
+        &mut self.joypad2
+    }
+
+    fn unmapped_access(&self, access: UnmappedAccess) {
+        if self.strict {
+            self.unmapped.borrow_mut().push(access);
+        }
+    }
+
+    /// Shared by [`Bus::mem_read`] and [`Bus::peek`]; a peek changes nothing on the bus.
+    fn read(&self, addr: u16, peek: bool) -> u8 {
+        let joypad = |joypad: &Joypad| if peek { joypad.peek() } else { joypad.read() };
+        let data = match addr {
+            RAM..=RAM_MIRRORS_END => self.cpu_vram[self.ram_index(addr)],
+
+            // Write-only: the CPU sees whatever was last on the bus.
+            APU_REGISTERS..=APU_REGISTERS_END | OAM_DMA => self.data_latch.get(),
+            APU_STATUS => self.apu.read_status(),
+            JOYPAD_1 => joypad(&self.joypad1) | (self.data_latch.get() & JOYPAD_OPEN_BUS_BITS),
+            JOYPAD_2 => joypad(&self.joypad2) | (self.data_latch.get() & JOYPAD_OPEN_BUS_BITS),
+
+            RESET_VECTOR..=RESET_VECTOR_END if self.reset_vector.is_some() => {
+                self.reset_vector.map_or(0, |vector| {
+                    vector.to_le_bytes()[usize::from(addr - RESET_VECTOR)]
+                })
             }
 
+            PRG_ROM..=PRG_ROM_END if self.rom.is_some() => self
+                .prg_rom_read(addr)
+                .unwrap_or_else(|| self.data_latch.get()),
+
+            // There is no PPU yet, so its registers are as unmapped as the rest.
             _ => {
-                println!("Ignoring invalid memory access at {addr:#04x}");
-                0
+                if !peek {
+                    self.unmapped_access(UnmappedAccess::Read { addr });
+                }
+                self.data_latch.get()
             }
+        };
+        self.game_genie
+            .iter()
+            .find_map(|code| code.patch(addr, data))
+            .unwrap_or(data)
+    }
+
+    /// PRG-ROM byte for `addr`; a 16 KiB image shows up in both halves of the window.
+    fn prg_rom_read(&self, addr: u16) -> Option<u8> {
+        let prg_rom = &self.rom.as_ref()?.prg_rom;
+        let index = usize::from(addr - PRG_ROM).checked_rem(prg_rom.len())?;
+        Some(prg_rom[index])
+    }
+
+    const fn ram_index(&self, addr: u16) -> usize {
+        if self.mirror_ram {
+            (addr & RAM_ADDR_BITS) as usize
+        } else {
+            addr as usize
         }
     }
+}
+
+impl Memory for Bus {
+    fn mem_read(&self, addr: u16) -> u8 {
+        let data = self.read(addr, false);
+        self.data_latch.set(data);
+        data
+    }
+
+    /// Like [`Bus::mem_read`] but leaves the data latch, the controllers and the strict-mode log
+    /// alone. PPU registers read as the data latch until there is a PPU to peek at.
+    fn peek(&self, addr: u16) -> u8 {
+        self.read(addr, true)
+    }
+
+    fn is_ram(&self, addr: u16) -> bool {
+        matches!(addr, RAM..=RAM_MIRRORS_END)
+    }
+
+    /// Bare programs have no cartridge to hold the vector, so the bus answers for it.
+    fn set_reset_vector(&mut self, addr: u16) {
+        self.reset_vector = Some(addr);
+    }
 
     fn mem_write(&mut self, addr: u16, data: u8) {
+        self.data_latch.set(data);
         match addr {
             RAM..=RAM_MIRRORS_END => {
-                let mirror_down_addr = addr & ELEVEN_BITS;
-                self.cpu_vram[mirror_down_addr as usize] = data;
+                let index = self.ram_index(addr);
+                self.cpu_vram[index] = data;
             }
 
-            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => {
-                let mirror_down_addr = addr & PPU_ADDR_BITS;
-                todo!();
+            APU_REGISTERS..=APU_REGISTERS_END | APU_STATUS | JOYPAD_2 => {
+                self.apu.write_register(addr, data);
             }
-
-            _ => {
-                println!("Ignoring invalid memory access at {addr:#04x}");
+            OAM_DMA => self.oam_dma_page = Some(data),
+            JOYPAD_1 => {
+                // The strobe line is shared by both controller ports.
+                self.joypad1.write(data);
+                self.joypad2.write(data);
             }
+
+            // NROM has no mapper registers, writes to ROM go nowhere.
+            PRG_ROM..=PRG_ROM_END if self.rom.is_some() => {}
+
+            // Including the PPU registers, until there is a PPU.
+            _ => self.unmapped_access(UnmappedAccess::Write { addr, data }),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cartridge::{Mirroring, CHR_ROM_PAGE_SIZE, PRG_ROM_PAGE_SIZE},
+        game_genie::ParseGameGenieError,
+        joypad::JoypadButton,
+    };
+
+    #[test]
+    fn test_ram_mirrors_by_default() {
+        let mut bus = Bus::default();
+        bus.mem_write(0x0800, 0x42);
+
+        assert_eq!(bus.mem_read(0x0000), 0x42);
+    }
+
+    #[test]
+    fn test_ram_top_mirror_round_trip() {
+        let mut bus = Bus::default();
+        bus.mem_write(0x1FFF, 0x42);
+
+        assert_eq!(bus.mem_read(0x07FF), 0x42);
+        assert_eq!(bus.mem_read(0x1FFF), 0x42);
+    }
+
+    #[test]
+    fn test_ram_unmirrored() {
+        let mut bus = Bus::default();
+        bus.set_mirror_ram(false);
+        bus.mem_write(0x0800, 0x42);
+
+        assert_eq!(bus.mem_read(0x0800), 0x42);
+        assert_eq!(bus.mem_read(0x0000), 0x00);
+    }
+
+    #[test]
+    fn test_permissive_unmapped_read_returns_data_latch() {
+        let mut bus = Bus::default();
+        bus.mem_write(0x0010, 0x5A);
+        bus.mem_read(0x0010);
+
+        assert_eq!(bus.mem_read(0x4020), 0x5A);
+        assert!(bus.take_unmapped_accesses().is_empty());
+    }
+
+    #[test]
+    fn test_strict_records_unmapped_access() {
+        let mut bus = Bus::default();
+        bus.set_strict(true);
+        bus.mem_write(0x4020, 0x11);
+        bus.mem_read(0x4020);
+
+        assert_eq!(
+            bus.take_unmapped_accesses(),
+            vec![
+                UnmappedAccess::Write {
+                    addr: 0x4020,
+                    data: 0x11
+                },
+                UnmappedAccess::Read { addr: 0x4020 },
+            ]
+        );
+        assert!(bus.take_unmapped_accesses().is_empty());
+    }
+
+    #[test]
+    fn test_ppu_registers_are_open_bus_until_there_is_a_ppu() {
+        let mut bus = Bus::default();
+        bus.set_strict(true);
+        bus.mem_write(0x2000, 0x80);
+
+        assert_eq!(bus.mem_read(0x3FFA), 0x80);
+        assert_eq!(
+            bus.take_unmapped_accesses(),
+            vec![
+                UnmappedAccess::Write {
+                    addr: 0x2000,
+                    data: 0x80
+                },
+                UnmappedAccess::Read { addr: 0x3FFA },
+            ]
+        );
+    }
+
+    /// NROM bus whose 32 KiB of PRG-ROM is all `original`, the byte a Game Genie code replaces.
+    fn bus_with_prg_filled(original: u8) -> Bus {
+        Bus::new(Rom {
+            prg_rom: vec![original; 2 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![0; CHR_ROM_PAGE_SIZE],
+            mapper: 0,
+            screen_mirroring: Mirroring::Horizontal,
+        })
+    }
+
+    #[test]
+    fn test_game_genie_six_letter_patch() -> Result<(), ParseGameGenieError> {
+        let mut bus = bus_with_prg_filled(0x77);
+        bus.add_game_genie("GOSSIP".parse()?); // 0xD1DD := 0x14
+
+        assert_eq!(bus.mem_read(0xD1DD), 0x14);
+        assert_eq!(bus.mem_read(0xD1DE), 0x77);
+        Ok(())
+    }
+
+    #[test]
+    fn test_game_genie_eight_letter_patch_needs_compare() -> Result<(), ParseGameGenieError> {
+        let code = "ZEXPYGLA".parse()?; // 0x94A7 := 0x02 if 0x03
+        let mut mismatched = bus_with_prg_filled(0x04);
+        mismatched.add_game_genie(code);
+        assert_eq!(mismatched.mem_read(0x94A7), 0x04);
+
+        let mut bus = bus_with_prg_filled(0x03);
+        bus.add_game_genie(code);
+        assert_eq!(bus.mem_read(0x94A7), 0x02);
+
+        bus.clear_game_genie();
+        assert_eq!(bus.mem_read(0x94A7), 0x03);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apu_and_joypad_writes_reach_their_subsystem() {
+        let mut bus = Bus::default();
+        bus.set_strict(true);
+        bus.mem_write(0x4000, 0x3F);
+        bus.mem_write(0x4014, 0x02);
+        bus.mem_write(0x4016, 0x01);
+
+        assert_eq!(bus.apu.register(0x4000), 0x3F);
+        assert_eq!(bus.oam_dma_page, Some(0x02));
+        assert!(bus.joypad1.strobe());
+        assert!(bus.joypad2.strobe());
+        assert!(bus.take_unmapped_accesses().is_empty());
+    }
+
+    #[test]
+    fn test_joypad_reads_buttons_in_order() {
+        let mut bus = Bus::default();
+        bus.joypad1_mut().set_button(JoypadButton::BUTTON_B, true);
+        bus.mem_write(0x4016, 0x01);
+        bus.mem_write(0x4016, 0x00);
+
+        let bits: Vec<u8> = (0..9).map(|_| bus.mem_read(0x4016) & 1).collect();
+        assert_eq!(bits, [0, 1, 0, 0, 0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_apu_register_reads_return_open_bus() {
+        let mut bus = Bus::default();
+        bus.set_strict(true);
+        bus.mem_write(0x4000, 0x3F);
+        bus.mem_write(0x0000, 0xA5);
+        bus.mem_read(0x0000);
+
+        assert_eq!(bus.mem_read(0x4000), 0xA5);
+        assert_eq!(bus.mem_read(0x4013), 0xA5);
+        assert!(bus.take_unmapped_accesses().is_empty());
+    }
+
+    #[test]
+    fn test_prg_rom_16k_is_mirrored() {
+        let mut prg_rom = vec![0; PRG_ROM_PAGE_SIZE];
+        prg_rom[0x0000] = 0x4C;
+        prg_rom[0x3FFC] = 0x00; // reset vector -> 0x8000
+        prg_rom[0x3FFD] = 0x80;
+        let mut bus = Bus::new(Rom {
+            prg_rom,
+            chr_rom: vec![0; CHR_ROM_PAGE_SIZE],
+            mapper: 0,
+            screen_mirroring: Mirroring::Horizontal,
+        });
+        bus.set_strict(true);
+
+        assert_eq!(bus.mem_read(0x8000), 0x4C);
+        assert_eq!(bus.mem_read(0xC000), 0x4C);
+        assert_eq!(bus.mem_read_u16(0xFFFC), 0x8000);
+
+        bus.mem_write(0x8000, 0x00);
+        assert_eq!(bus.mem_read(0x8000), 0x4C);
+        assert!(bus.take_unmapped_accesses().is_empty());
+    }
+
+    #[test]
+    fn test_peek_has_no_side_effects() {
+        let mut bus = Bus::default();
+        bus.set_strict(true);
+        bus.joypad1_mut().set_button(JoypadButton::BUTTON_A, true);
+        bus.mem_write(0x0010, 0x5A);
+        bus.mem_read(0x0010);
+
+        assert_eq!(bus.peek(0x4016) & 1, 1);
+        assert_eq!(bus.peek(0x4016) & 1, 1);
+        assert_eq!(bus.peek(0x2002), 0x5A);
+        assert_eq!(bus.peek(0x4020), 0x5A);
+        bus.mem_write(0x0000, 0x00);
+        bus.peek(0x0010);
+        assert_eq!(bus.mem_read(0x4020), 0x00);
+
+        let accesses = bus.take_unmapped_accesses();
+        assert_eq!(accesses, vec![UnmappedAccess::Read { addr: 0x4020 }]);
+        assert_eq!(bus.mem_read(0x4016) & 1, 1);
+        assert_eq!(bus.mem_read(0x4016) & 1, 0);
+    }
+}
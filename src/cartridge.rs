@@ -0,0 +1,286 @@
+//! iNES (`.nes`) cartridge images.
+//!
+//! <https://www.nesdev.org/wiki/INES>
+
+use std::fmt;
+
+pub(crate) const MAGIC: [u8; 4] = *b"NES\x1A";
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+pub const PRG_ROM_PAGE_SIZE: usize = 16 * 1024;
+pub const CHR_ROM_PAGE_SIZE: usize = 8 * 1024;
+
+const FLAG6_VERTICAL_MIRRORING: u8 = 0b0000_0001;
+const FLAG6_TRAINER: u8 = 0b0000_0100;
+const FLAG6_FOUR_SCREEN: u8 = 0b0000_1000;
+/// Bits 2-3 of flags 7 are `0b10` in an NES 2.0 header.
+const FLAG7_VERSION_BITS: u8 = 0b0000_1100;
+const FLAG7_NES2: u8 = 0b0000_1000;
+
+/// How the PPU's nametables are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    Vertical,
+    Horizontal,
+    FourScreen,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rom {
+    pub prg_rom: Vec<u8>,
+    pub chr_rom: Vec<u8>,
+    pub mapper: u8,
+    pub screen_mirroring: Mirroring,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomError {
+    /// The file does not start with `NES\x1A`.
+    BadMagic,
+    /// The header promises more bytes than the file has.
+    Truncated { expected: usize, actual: usize },
+    /// A NES 2.0 header gives sizes too big to address.
+    Oversized,
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => f.write_str("not an iNES file, missing the NES<EOF> magic"),
+            Self::Truncated { expected, actual } => {
+                write!(
+                    f,
+                    "iNES file is truncated: expected {expected} bytes, got {actual}"
+                )
+            }
+            Self::Oversized => f.write_str("NES 2.0 ROM size does not fit in memory"),
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+impl Rom {
+    /// Parse an iNES or NES 2.0 image: header, optional trainer (skipped), PRG-ROM, then CHR-ROM.
+    /// Of the NES 2.0 extensions only the wider ROM sizes are read.
+    pub fn from_ines(bytes: &[u8]) -> Result<Self, RomError> {
+        let truncated = |expected| RomError::Truncated {
+            expected,
+            actual: bytes.len(),
+        };
+
+        let header = bytes
+            .get(..HEADER_SIZE)
+            .ok_or_else(|| truncated(HEADER_SIZE))?;
+        if header[..MAGIC.len()] != MAGIC {
+            return Err(RomError::BadMagic);
+        }
+        let (flags6, flags7) = (header[6], header[7]);
+        // NES 2.0 keeps the high nibbles of both sizes in byte 9, plain iNES leaves it zero.
+        let size_msb = if flags7 & FLAG7_VERSION_BITS == FLAG7_NES2 {
+            header[9]
+        } else {
+            0
+        };
+
+        let mapper = (flags7 & 0xF0) | (flags6 >> 4);
+        let screen_mirroring = if flags6 & FLAG6_FOUR_SCREEN != 0 {
+            Mirroring::FourScreen
+        } else if flags6 & FLAG6_VERTICAL_MIRRORING != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+
+        let prg_size = rom_size(header[4], size_msb & 0x0F, PRG_ROM_PAGE_SIZE)?;
+        let chr_size = rom_size(header[5], size_msb >> 4, CHR_ROM_PAGE_SIZE)?;
+        let trainer_size = if flags6 & FLAG6_TRAINER == 0 {
+            0
+        } else {
+            TRAINER_SIZE
+        };
+
+        let prg_start = HEADER_SIZE + trainer_size;
+        let chr_start = prg_start.checked_add(prg_size).ok_or(RomError::Oversized)?;
+        let end = chr_start.checked_add(chr_size).ok_or(RomError::Oversized)?;
+        if bytes.len() < end {
+            return Err(truncated(end));
+        }
+
+        Ok(Self {
+            prg_rom: bytes[prg_start..chr_start].to_vec(),
+            chr_rom: bytes[chr_start..end].to_vec(),
+            mapper,
+            screen_mirroring,
+        })
+    }
+
+    /// PRG-ROM split into the 16 KiB banks a mapper switches between.
+    pub fn prg_banks(&self) -> impl Iterator<Item = &[u8]> {
+        self.prg_rom.chunks(PRG_ROM_PAGE_SIZE)
+    }
+
+    /// CHR-ROM split into 8 KiB banks.
+    pub fn chr_banks(&self) -> impl Iterator<Item = &[u8]> {
+        self.chr_rom.chunks(CHR_ROM_PAGE_SIZE)
+    }
+}
+
+/// Size in bytes from the header's page count and its NES 2.0 high nibble. A nibble of 0xF
+/// switches to the exponent-multiplier form, `2^E * (MM * 2 + 1)` with `lsb` laid out `EEEEEEMM`.
+fn rom_size(lsb: u8, msb: u8, page_size: usize) -> Result<usize, RomError> {
+    if msb == 0x0F {
+        let multiplier = usize::from(lsb & 0b11) * 2 + 1;
+        1_usize
+            .checked_shl(u32::from(lsb >> 2))
+            .and_then(|power| power.checked_mul(multiplier))
+            .ok_or(RomError::Oversized)
+    } else {
+        Ok(usize::from(u16::from_le_bytes([lsb, msb])) * page_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// NROM image with one PRG page filled with `0xEA` and one CHR page filled with `0x55`.
+    fn nrom(flags6: u8, flags7: u8) -> Vec<u8> {
+        let mut bytes = vec![b'N', b'E', b'S', 0x1A, 1, 1, flags6, flags7];
+        bytes.resize(HEADER_SIZE, 0);
+        if flags6 & FLAG6_TRAINER != 0 {
+            bytes.resize(HEADER_SIZE + TRAINER_SIZE, 0xFF);
+        }
+        bytes.extend([0xEA; PRG_ROM_PAGE_SIZE]);
+        bytes.extend([0x55; CHR_ROM_PAGE_SIZE]);
+        bytes
+    }
+
+    #[test]
+    fn test_from_ines_nrom() -> Result<(), RomError> {
+        let rom = Rom::from_ines(&nrom(FLAG6_VERTICAL_MIRRORING, 0))?;
+
+        assert_eq!(rom.mapper, 0);
+        assert_eq!(rom.screen_mirroring, Mirroring::Vertical);
+        assert_eq!(rom.prg_rom, [0xEA; PRG_ROM_PAGE_SIZE]);
+        assert_eq!(rom.chr_rom, [0x55; CHR_ROM_PAGE_SIZE]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_ines_skips_trainer_and_reads_mapper() -> Result<(), RomError> {
+        let rom = Rom::from_ines(&nrom(FLAG6_TRAINER | FLAG6_FOUR_SCREEN | 0x10, 0x40))?;
+
+        assert_eq!(rom.mapper, 0x41);
+        assert_eq!(rom.screen_mirroring, Mirroring::FourScreen);
+        assert_eq!(rom.prg_rom, [0xEA; PRG_ROM_PAGE_SIZE]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_banks_split_on_page_size() -> Result<(), RomError> {
+        let mut bytes = vec![b'N', b'E', b'S', 0x1A, 2, 3];
+        bytes.resize(HEADER_SIZE, 0);
+        for bank in 0..2 {
+            bytes.extend([bank; PRG_ROM_PAGE_SIZE]);
+        }
+        for bank in 0..3 {
+            bytes.extend([0x10 | bank; CHR_ROM_PAGE_SIZE]);
+        }
+        let rom = Rom::from_ines(&bytes)?;
+
+        let prg: Vec<_> = rom
+            .prg_banks()
+            .map(|bank| (bank.len(), bank.first().copied()))
+            .collect();
+        assert_eq!(
+            prg,
+            [(PRG_ROM_PAGE_SIZE, Some(0)), (PRG_ROM_PAGE_SIZE, Some(1))]
+        );
+        let chr: Vec<_> = rom
+            .chr_banks()
+            .map(|bank| (bank.len(), bank.first().copied()))
+            .collect();
+        assert_eq!(
+            chr,
+            [
+                (CHR_ROM_PAGE_SIZE, Some(0x10)),
+                (CHR_ROM_PAGE_SIZE, Some(0x11)),
+                (CHR_ROM_PAGE_SIZE, Some(0x12))
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_ines_nes2_sizes() -> Result<(), RomError> {
+        assert_eq!(
+            Rom::from_ines(&nrom(0, FLAG7_NES2))?,
+            Rom::from_ines(&nrom(0, 0))?
+        );
+
+        // 0x100 PRG pages and 0x201 CHR pages, past what the single iNES byte can count.
+        let mut bytes = vec![b'N', b'E', b'S', 0x1A, 0x00, 0x01, 0, FLAG7_NES2, 0, 0x21];
+        bytes.resize(HEADER_SIZE, 0);
+        bytes.resize(HEADER_SIZE + 0x100 * PRG_ROM_PAGE_SIZE, 0xEA);
+        bytes.resize(bytes.len() + 0x201 * CHR_ROM_PAGE_SIZE, 0x55);
+        let rom = Rom::from_ines(&bytes)?;
+        assert_eq!(rom.prg_rom.len(), 0x100 * PRG_ROM_PAGE_SIZE);
+        assert_eq!(rom.chr_rom.len(), 0x201 * CHR_ROM_PAGE_SIZE);
+        assert_eq!(rom.prg_rom.last(), Some(&0xEA));
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_ines_nes2_exponent_multiplier() -> Result<(), RomError> {
+        // PRG 2^10 * 3 = 3 KiB, CHR 2^9 * 1 = 512 bytes.
+        let mut bytes = vec![
+            b'N',
+            b'E',
+            b'S',
+            0x1A,
+            10 << 2 | 1,
+            9 << 2,
+            0,
+            FLAG7_NES2,
+            0,
+            0xFF,
+        ];
+        bytes.resize(HEADER_SIZE, 0);
+        bytes.extend([0xEA; 3 * 1024]);
+        bytes.extend([0x55; 512]);
+        let rom = Rom::from_ines(&bytes)?;
+
+        assert_eq!(rom.prg_rom, [0xEA; 3 * 1024]);
+        assert_eq!(rom.chr_rom, [0x55; 512]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_ines_errors() {
+        let mut bad_magic = nrom(0, 0);
+        bad_magic[3] = 0x1B;
+        assert_eq!(Rom::from_ines(&bad_magic), Err(RomError::BadMagic));
+
+        let mut oversized = nrom(0, FLAG7_NES2);
+        oversized[4] = 0xFF;
+        oversized[9] = 0x0F;
+        assert_eq!(Rom::from_ines(&oversized), Err(RomError::Oversized));
+
+        let full = nrom(0, 0);
+        assert_eq!(
+            Rom::from_ines(&full[..full.len() - 1]),
+            Err(RomError::Truncated {
+                expected: full.len(),
+                actual: full.len() - 1
+            })
+        );
+        assert_eq!(
+            Rom::from_ines(b"NES"),
+            Err(RomError::Truncated {
+                expected: HEADER_SIZE,
+                actual: 3
+            })
+        );
+    }
+}
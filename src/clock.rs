@@ -0,0 +1,100 @@
+//! Real-time pacing for frontends.
+//!
+//! The CPU itself runs as fast as the host allows. A frontend that wants real-time speed keeps a
+//! [`Pacer`] next to the CPU and asks it how long to sleep after each batch of instructions.
+
+use std::time::{Duration, Instant};
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockConfig {
+    pub hz: u64,
+}
+
+impl ClockConfig {
+    /// NTSC 2A03, master clock / 12.
+    pub const NTSC: Self = Self { hz: 1_789_773 };
+
+    /// Wall-clock time the real hardware would take to run `cycles`.
+    #[must_use]
+    pub fn cycles_to_duration(self, cycles: u64) -> Duration {
+        let nanos = u128::from(cycles) * NANOS_PER_SECOND / u128::from(self.hz.max(1));
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self::NTSC
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Pacer {
+    clock: ClockConfig,
+    start: Instant,
+    start_cycles: u64,
+}
+
+impl Pacer {
+    /// Start pacing from `cycles`, the CPU's accumulated cycle count right now.
+    #[must_use]
+    pub fn new(clock: ClockConfig, cycles: u64) -> Self {
+        Self {
+            clock,
+            start: Instant::now(),
+            start_cycles: cycles,
+        }
+    }
+
+    #[must_use]
+    pub const fn clock(&self) -> ClockConfig {
+        self.clock
+    }
+
+    /// How long to sleep so that `cycles_delta` emulated cycles take as long as they would on
+    /// hardware, given `elapsed` real time was already spent emulating them.
+    #[must_use]
+    pub fn sleep_for(&self, cycles_delta: u64, elapsed: Duration) -> Duration {
+        self.clock
+            .cycles_to_duration(cycles_delta)
+            .saturating_sub(elapsed)
+    }
+
+    /// [`Pacer::sleep_for`] measured against the wall clock since the pacer was created.
+    #[must_use]
+    pub fn sleep_until_cycles(&self, cycles: u64) -> Duration {
+        self.sleep_for(
+            cycles.saturating_sub(self.start_cycles),
+            self.start.elapsed(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ntsc_one_second_of_cycles() {
+        let clock = ClockConfig::NTSC;
+        assert_eq!(clock.cycles_to_duration(1_789_773), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_pacer_sleep_for_cycle_delta() {
+        let pacer = Pacer::new(ClockConfig { hz: 1_000_000 }, 0);
+
+        // 29_780 cycles at 1 MHz is 29.78ms; 10ms were already spent emulating them.
+        assert_eq!(
+            pacer.sleep_for(29_780, Duration::from_millis(10)),
+            Duration::from_micros(19_780)
+        );
+        // Running behind schedule never asks for a negative sleep.
+        assert_eq!(
+            pacer.sleep_for(1_000, Duration::from_millis(5)),
+            Duration::ZERO
+        );
+    }
+}
@@ -20,17 +20,42 @@
 
 // Index Register Y (Y) - similar use cases as register X.
 
-use self::{cpuflags::CpuFlags, memory::Memory};
+use self::{
+    call_graph::CallHook,
+    cpuflags::CpuFlags,
+    history::History,
+    init_map::InitMap,
+    memory::{FlatMemory, Memory},
+    stack_depth::StackDepth,
+    symbols::SymbolTable,
+};
 use crate::{
     addressing_mode::AddressingMode,
-    opcode::{self, mnemonic::Mnemonic, OpCode},
+    bus::Bus,
+    joypad::JoypadButton,
+    opcode::{self, mnemonic::Mnemonic, OpCode, UnknownOpcode},
+};
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Range,
 };
 
+pub mod call_graph;
 pub mod memory;
+pub mod symbols;
 
 mod cpuflags;
+#[cfg(feature = "decimal")]
+mod decimal;
+mod disassemble;
+mod history;
+mod init_map;
 mod instructions;
-mod opcode_array;
+mod interrupt;
+pub(crate) mod opcode_array;
+mod stack_depth;
+mod trace;
 
 // https://archive.nes.science/nesdev-forums/f3/t715.xhtml#p7591
 // by WedNESday on 2005-12-21 (#7591)
@@ -46,7 +71,11 @@ mod opcode_array;
 //
 // It started at zero. As part of the reset process the CPU decremented S three times. By the
 // time the first program instruction is executed S is $FD (0 minus 3).
-const STACK_RESET: u8 = 0xFD; // 0 - 3 = 0xfd (Wrapping!)
+//
+// Real power-on leaves S undefined; zero is the usual convention.
+const STACK_POWER_ON: u8 = 0x00;
+const RESET_STACK_DECREMENTS: u8 = 3;
+const STACK_RESET: u8 = STACK_POWER_ON.wrapping_sub(RESET_STACK_DECREMENTS); // 0 - 3 = 0xfd (Wrapping!)
 
 // Stack Pointer - Memory space [0x0100 .. 0x01FF] is used for stack.
 const STACK_START: u16 = 0x0100;
@@ -56,158 +85,780 @@ const STACK_MEMORY_END: u16 = 0x01FF;
 const PRG_ROM_START_ADDR: u16 = 0x0600;
 const PRG_ROM_EXEC_ADDR: u16 = 0xFFFC;
 
+// Interrupt vectors, stored little-endian like the reset vector above.
+const NMI_VECTOR: u16 = 0xFFFA;
+const IRQ_VECTOR: u16 = 0xFFFE;
+
+// 0x0000 ..= 0xFFFF
+const MEMORY_SIZE: usize = 0x1_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadError {
+    /// The reset vector points outside the loaded program, running would execute garbage.
+    ResetVectorOutOfRange { vector: u16 },
+    /// The program would run into the reset vector at 0xFFFC, which `load` writes afterwards.
+    OverlapsResetVector { end: usize },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ResetVectorOutOfRange { vector } => {
+                write!(
+                    f,
+                    "reset vector {vector:#06x} points outside the loaded program"
+                )
+            }
+            Self::OverlapsResetVector { end } => {
+                write!(
+                    f,
+                    "program ending at {end:#06x} overlaps the reset vector at {PRG_ROM_EXEC_ADDR:#06x}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Stack pointer wrap recorded in debug builds, see [`CPU::take_stack_errors`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackError {
+    /// Pushed with SP at 0x00, SP wrapped to 0xFF.
+    Overflow { pc: u16 },
+    /// Pulled with SP at 0xFF, SP wrapped to 0x00.
+    Underflow { pc: u16 },
+}
+
+impl fmt::Display for StackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Overflow { pc } => write!(f, "stack overflow at {pc:#06x}"),
+            Self::Underflow { pc } => write!(f, "stack underflow at {pc:#06x}"),
+        }
+    }
+}
+
+impl std::error::Error for StackError {}
+
+/// Why [`CPU::run_until_write`] stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStop {
+    /// The instruction at `pc` wrote `value` to the watched address.
+    Write { pc: u16, value: u8 },
+    /// The CPU halted (BRK under [`BrkPolicy::Halt`], or a trapped fetch) before writing.
+    Halted,
+    /// `max_instructions` ran without a write.
+    InstructionLimit,
+}
+
+/// One instruction executed by [`CPU::step`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepResult {
+    /// Cycles the step took, including any interrupt serviced before the instruction.
+    pub cycles: u8,
+    pub pc_before: u16,
+    /// `None` when the CPU halted before decoding anything (a trapped fetch, or an unknown byte
+    /// under [`IllegalOpcodePolicy::Halt`]).
+    pub opcode: Option<OpCode>,
+    /// The CPU stopped instead of executing `opcode`, see [`CPU::step`].
+    pub halted: bool,
+}
+
+impl StepResult {
+    /// The opcode that ran, `None` if the CPU halted.
+    #[must_use]
+    pub const fn executed(&self) -> Option<OpCode> {
+        if self.halted {
+            None
+        } else {
+            self.opcode
+        }
+    }
+}
+
+/// Outcome of [`CPU::execute`], before it is timed into a [`StepResult`].
+enum Executed {
+    Ran(OpCode),
+    Halted(Option<OpCode>),
+}
+
+/// What [`CPU::step`] does with a byte that is not in the opcode table.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IllegalOpcodePolicy {
+    /// [`CPU::try_step`] and [`CPU::try_run`] report an [`UnknownOpcode`], [`CPU::step`] and
+    /// [`CPU::run`] panic with it.
+    #[default]
+    Error,
+    /// Stop the run loop, like BRK under [`BrkPolicy::Halt`].
+    Halt,
+    /// Skip the byte as a one-byte NOP.
+    Nop,
+}
+
+/// Which opcodes [`CPU::step`] executes, see [`CPU::set_opcode_set`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OpcodeSet {
+    /// Only the documented 6502 instructions; the unofficial ones go to the
+    /// [`IllegalOpcodePolicy`] like any other byte outside the table.
+    Official,
+    /// Everything in the opcode table, unofficial instructions included.
+    #[default]
+    Full,
+}
+
+/// What [`CPU::step`] does on BRK.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BrkPolicy {
+    /// Take the software interrupt through the IRQ/BRK vector at 0xFFFE.
+    #[default]
+    Interrupt,
+    /// Stop the run loop, for bare programs that end in BRK.
+    Halt,
+}
+
+/// Carry going into and coming out of the last ADC/SBC, see [`CPU::last_arithmetic_carry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArithmeticCarry {
+    pub carry_in: bool,
+    pub carry_out: bool,
+}
+
 #[derive(Debug)]
-pub struct CPU {
+pub struct CPU<M: Memory = FlatMemory> {
     register_a: u8,
     register_x: u8,
     register_y: u8,
     status: CpuFlags,
     program_counter: u16,
     stack_ptr: u8,
-    memory: [u8; 0xFFFF],
+    memory: M,
+    stack_depth: Option<StackDepth>,
+    call_hook: Option<CallHook>,
+    loaded: Option<Range<usize>>,
+    init_map: Option<InitMap>,
+    uninitialized_fetch: Option<u16>,
+    stack_errors: Vec<StackError>,
+    last_carry: Option<ArithmeticCarry>,
+    write_watch: Option<(u16, Option<u8>)>,
+    history: Option<History>,
+    symbols: SymbolTable,
+    irq_line: bool,
+    /// Set by the interrupt poll at the end of an instruction, serviced before the next one.
+    irq_due: bool,
+    nmi_pending: bool,
+    brk_policy: BrkPolicy,
+    illegal_opcode_policy: IllegalOpcodePolicy,
+    opcode_set: OpcodeSet,
+    cycles: u64,
 }
 
 impl Default for CPU {
     fn default() -> Self {
+        Self::new(FlatMemory::default())
+    }
+}
+
+impl<M: Memory> CPU<M> {
+    /// CPU running on top of `memory`, e.g. a [`FlatMemory`] for bare 6502 code.
+    pub fn new(memory: M) -> Self {
         Self {
             register_a: 0,
             register_x: 0,
             register_y: 0,
             program_counter: 0,
-            stack_ptr: STACK_RESET,
+            stack_ptr: STACK_POWER_ON,
             status: CpuFlags::default(),
-            memory: [0; 0xFFFF],
+            memory,
+            stack_depth: None,
+            call_hook: None,
+            loaded: None,
+            init_map: None,
+            uninitialized_fetch: None,
+            stack_errors: Vec::new(),
+            last_carry: None,
+            write_watch: None,
+            history: None,
+            symbols: SymbolTable::default(),
+            irq_line: false,
+            irq_due: false,
+            nmi_pending: false,
+            brk_policy: BrkPolicy::Interrupt,
+            illegal_opcode_policy: IllegalOpcodePolicy::Error,
+            opcode_set: OpcodeSet::Full,
+            cycles: 0,
         }
     }
+
+    pub const fn set_illegal_opcode_policy(&mut self, policy: IllegalOpcodePolicy) {
+        self.illegal_opcode_policy = policy;
+    }
+
+    pub const fn set_brk_policy(&mut self, policy: BrkPolicy) {
+        self.brk_policy = policy;
+    }
+
+    pub const fn set_opcode_set(&mut self, opcode_set: OpcodeSet) {
+        self.opcode_set = opcode_set;
+    }
+
+    /// CPU cycles executed since the CPU was created.
+    #[must_use]
+    pub const fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub const fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    pub const fn memory_mut(&mut self) -> &mut M {
+        &mut self.memory
+    }
+}
+
+/// Hashes the architectural state (registers, flags, PC, SP and memory), not the debugging aids,
+/// so a repeated hash after a step means the program is looping without progress.
+impl<M: Memory + Hash> Hash for CPU<M> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.register_a.hash(state);
+        self.register_x.hash(state);
+        self.register_y.hash(state);
+        self.status.hash(state);
+        self.program_counter.hash(state);
+        self.stack_ptr.hash(state);
+        self.memory.hash(state);
+    }
 }
 
-impl Memory for CPU {
+impl<M: Memory> Memory for CPU<M> {
     fn mem_read(&self, addr: u16) -> u8 {
-        self.memory[addr as usize]
+        self.memory.mem_read(addr)
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.memory.peek(addr)
+    }
+
+    fn is_ram(&self, addr: u16) -> bool {
+        self.memory.is_ram(addr)
+    }
+
+    fn set_reset_vector(&mut self, addr: u16) {
+        self.memory.set_reset_vector(addr);
     }
 
     fn mem_write(&mut self, addr: u16, data: u8) {
-        self.memory[addr as usize] = data;
+        self.record_history_write(addr);
+        self.memory.mem_write(addr, data);
+        if let Some((watched, hit)) = &mut self.write_watch {
+            if *watched == addr {
+                *hit = Some(data);
+            }
+        }
+        self.mark_initialized(addr.into()..usize::from(addr) + 1);
     }
 }
 
-impl CPU {
+/// Whether the handler `mnemonic` dispatches to actually executes `mode`.
+///
+/// Handlers only branch on the modes they expect (e.g. `jmp` on Absolute/Indirect, the shifts on
+/// Accumulator vs. memory), anything else would silently misbehave or panic.
+const fn handles_mode(mnemonic: Mnemonic, mode: AddressingMode) -> bool {
+    use AddressingMode as Mode;
+
+    let memory_operand = matches!(
+        mode,
+        Mode::Immediate
+            | Mode::ZeroPage
+            | Mode::ZeroPage_X
+            | Mode::ZeroPage_Y
+            | Mode::Absolute
+            | Mode::Absolute_X
+            | Mode::Absolute_Y
+            | Mode::Indirect_X
+            | Mode::Indirect_Y
+    );
+
+    match mnemonic {
+        Mnemonic::Adc
+        | Mnemonic::And
+        | Mnemonic::Bit
+        | Mnemonic::Cmp
+        | Mnemonic::Cpx
+        | Mnemonic::Cpy
+        | Mnemonic::Dec
+        | Mnemonic::Eor
+        | Mnemonic::Inc
+        | Mnemonic::Lda
+        | Mnemonic::Ldx
+        | Mnemonic::Ldy
+        | Mnemonic::Ora
+        | Mnemonic::Sbc
+        | Mnemonic::Sta
+        | Mnemonic::Stx
+        | Mnemonic::Sty
+        | Mnemonic::Dcp
+        | Mnemonic::Isb
+        | Mnemonic::Lax
+        | Mnemonic::Rla
+        | Mnemonic::Rra
+        | Mnemonic::Sax
+        | Mnemonic::Slo
+        | Mnemonic::Sre => memory_operand,
+        Mnemonic::Asl | Mnemonic::Lsr | Mnemonic::Rol | Mnemonic::Ror => {
+            memory_operand || matches!(mode, Mode::Accumulator)
+        }
+        // the unofficial NOPs read an operand and discard it
+        Mnemonic::Nop => memory_operand || matches!(mode, Mode::Implicit),
+        Mnemonic::Jmp => matches!(mode, Mode::Absolute | Mode::Indirect),
+        Mnemonic::Jsr => matches!(mode, Mode::Absolute),
+        Mnemonic::Bcc
+        | Mnemonic::Bcs
+        | Mnemonic::Beq
+        | Mnemonic::Bmi
+        | Mnemonic::Bne
+        | Mnemonic::Bpl
+        | Mnemonic::Bvc
+        | Mnemonic::Bvs => matches!(mode, Mode::Relative),
+        _ => matches!(mode, Mode::Implicit),
+    }
+}
+
+/// Indexed reads of these take an extra cycle when the index carries into the high byte; stores
+/// and read-modify-write instructions always spend that cycle and have it in their base count.
+const fn has_page_cross_penalty(mnemonic: Mnemonic) -> bool {
+    matches!(
+        mnemonic,
+        Mnemonic::Adc
+            | Mnemonic::And
+            | Mnemonic::Cmp
+            | Mnemonic::Eor
+            | Mnemonic::Lda
+            | Mnemonic::Ldx
+            | Mnemonic::Ldy
+            | Mnemonic::Ora
+            | Mnemonic::Sbc
+            | Mnemonic::Lax
+            | Mnemonic::Nop
+    )
+}
+
+/// Extra cycles of a branch reported as `(taken, page_crossed)`: +1 if taken, +1 more if the
+/// target is on another page.
+fn branch_penalty((taken, page_crossed): (bool, bool)) -> u64 {
+    u64::from(taken) + u64::from(page_crossed)
+}
+
+const fn page_crossed(from: u16, to: u16) -> bool {
+    from & 0xFF00 != to & 0xFF00
+}
+
+impl<M: Memory> CPU<M> {
     pub fn run_with_callback<F>(&mut self, mut callback: F)
     where
         F: FnMut(&mut Self),
     {
-        loop {
-            let raw_opcode = self.mem_read(self.program_counter);
-            self.program_counter += 1;
-            let program_counter_state = self.program_counter;
-
-            let opcode = opcode_array::decode(raw_opcode);
-            match opcode.mnemonic {
-                Mnemonic::Adc => self.adc(opcode.mode),
-                Mnemonic::And => self.and(opcode.mode),
-                Mnemonic::Asl => self.asl(opcode.mode),
-                Mnemonic::Bcc => self.bcc(),
-                Mnemonic::Bcs => self.bcs(),
-                Mnemonic::Beq => self.beq(),
-                Mnemonic::Bit => self.bit(opcode.mode),
-                Mnemonic::Bmi => self.bmi(),
-                Mnemonic::Bne => self.bne(),
-                Mnemonic::Bpl => self.bpl(),
-                Mnemonic::Brk => return,
-                Mnemonic::Bvc => self.bvc(),
-                Mnemonic::Bvs => self.bvs(),
-                Mnemonic::Clc => self.clc(),
-                Mnemonic::Cld => self.cld(),
-                Mnemonic::Cli => self.cli(),
-                Mnemonic::Clv => self.clv(),
-                Mnemonic::Cmp => self.compare(opcode.mode, self.register_a),
-                Mnemonic::Cpx => self.compare(opcode.mode, self.register_x),
-                Mnemonic::Cpy => self.compare(opcode.mode, self.register_y),
-                Mnemonic::Dec => self.dec(opcode.mode),
-                Mnemonic::Dex => self.dex(opcode.mode),
-                Mnemonic::Dey => self.dey(opcode.mode),
-                Mnemonic::Eor => self.eor(opcode.mode),
-                Mnemonic::Inc => self.inc(opcode.mode),
-                Mnemonic::Inx => self.inx(),
-                Mnemonic::Iny => self.iny(),
-                Mnemonic::Jmp => self.jmp(opcode.mode),
-                Mnemonic::Jsr => self.jsr(),
-                Mnemonic::Lda => self.lda(opcode.mode),
-                Mnemonic::Ldx => self.ldx(opcode.mode),
-                Mnemonic::Ldy => self.ldy(opcode.mode),
-                Mnemonic::Lsr => self.lsr(opcode.mode),
-                Mnemonic::Nop => (),
-                Mnemonic::Ora => self.ora(opcode.mode),
-                Mnemonic::Pha => self.pha(opcode.mode),
-                Mnemonic::Php => self.php(opcode.mode),
-                Mnemonic::Pla => self.pla(opcode.mode),
-                Mnemonic::Plp => self.plp(opcode.mode),
-                Mnemonic::Rol => self.rol(opcode.mode),
-                Mnemonic::Ror => self.ror(opcode.mode),
-                Mnemonic::Rti => self.rti(),
-                Mnemonic::Rts => self.rts(),
-                Mnemonic::Sbc => self.sbc(opcode.mode),
-                Mnemonic::Sec => self.sec(),
-                Mnemonic::Sed => self.sed(),
-                Mnemonic::Sei => self.sei(),
-                Mnemonic::Sta => self.sta(opcode.mode),
-                Mnemonic::Stx => self.stx(opcode.mode),
-                Mnemonic::Sty => self.sty(opcode.mode),
-                Mnemonic::Tax => self.tax(),
-                Mnemonic::Tay => self.tay(),
-                Mnemonic::Tsx => todo!(),
-                Mnemonic::Txa => self.txa(),
-                Mnemonic::Txs => todo!(),
-                Mnemonic::Tya => self.tya(),
+        self.run_with_opcode_callback(|cpu, _| callback(cpu));
+    }
+
+    /// Like [`CPU::run_with_callback`], but the callback also receives the opcode that just ran.
+    pub fn run_with_opcode_callback<F>(&mut self, mut callback: F)
+    where
+        F: FnMut(&mut Self, &OpCode),
+    {
+        while let Some(opcode) = self.step().executed() {
+            callback(self, &opcode);
+        }
+    }
+
+    /// Execute the single instruction at the program counter.
+    ///
+    /// The result is `halted` when the CPU stopped instead (BRK under [`BrkPolicy::Halt`], or a
+    /// trapped fetch from uninitialized memory).
+    ///
+    /// # Panics
+    ///
+    /// On a byte outside the opcode table under [`IllegalOpcodePolicy::Error`].
+    pub fn step(&mut self) -> StepResult {
+        self.try_step().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// [`CPU::step`] that reports an unknown opcode instead of panicking. The program counter is
+    /// left on the offending byte.
+    pub fn try_step(&mut self) -> Result<StepResult, UnknownOpcode> {
+        let pc_before = self.program_counter;
+        let cycles_before = self.cycles;
+
+        self.begin_history_entry();
+        let executed = self.execute();
+        self.end_history_entry();
+
+        let (opcode, halted) = match executed? {
+            Executed::Ran(opcode) => (Some(opcode), false),
+            Executed::Halted(opcode) => (opcode, true),
+        };
+        Ok(StepResult {
+            cycles: u8::try_from(self.cycles - cycles_before).unwrap_or(u8::MAX),
+            pc_before,
+            opcode,
+            halted,
+        })
+    }
+
+    fn execute(&mut self) -> Result<Executed, UnknownOpcode> {
+        self.poll_nmi();
+        self.service_due_irq();
+        let irq_disabled = self.status.contains(CpuFlags::INTERUPT_DISABLE);
+
+        if self.is_uninitialized(self.program_counter) {
+            self.uninitialized_fetch = Some(self.program_counter);
+            return Ok(Executed::Halted(None));
+        }
+
+        let opcode_pc = self.program_counter;
+        let raw_opcode = self.mem_read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
+        let program_counter_state = self.program_counter;
+
+        let opcode = match opcode_array::try_decode(raw_opcode) {
+            Ok(opcode) if !opcode.official && self.opcode_set == OpcodeSet::Official => {
+                let err = UnknownOpcode {
+                    code: raw_opcode,
+                    pc: None,
+                };
+                return self.illegal_opcode(err, opcode_pc);
+            }
+            Ok(opcode) => opcode,
+            Err(err) => return self.illegal_opcode(err, opcode_pc),
+        };
+        self.cycles += u64::from(opcode.cycles);
+        if has_page_cross_penalty(opcode.mnemonic) && self.operand_page_crossed(opcode.mode) {
+            self.cycles += 1;
+        }
+        debug_assert!(
+            handles_mode(opcode.mnemonic, opcode.mode),
+            "{opcode:?} has a mode its handler does not execute"
+        );
+        if opcode.mnemonic == Mnemonic::Brk && self.brk_policy == BrkPolicy::Halt {
+            return Ok(Executed::Halted(Some(opcode)));
+        }
+        self.dispatch(opcode);
+
+        if program_counter_state == self.program_counter {
+            // minus one since we inc when mem_read @ start of fn
+            self.program_counter = self.program_counter.wrapping_add(u16::from(opcode.len - 1));
+        }
+
+        self.poll_irq(opcode.mnemonic, irq_disabled);
+        Ok(Executed::Ran(opcode))
+    }
+
+    /// Run the handler for `opcode`, with the program counter on its first operand byte.
+    fn dispatch(&mut self, opcode: OpCode) {
+        match opcode.mnemonic {
+            Mnemonic::Adc => self.adc(opcode.mode),
+            Mnemonic::And => self.and(opcode.mode),
+            Mnemonic::Asl => self.asl(opcode.mode),
+            Mnemonic::Bcc => self.cycles += branch_penalty(self.bcc()),
+            Mnemonic::Bcs => self.cycles += branch_penalty(self.bcs()),
+            Mnemonic::Beq => self.cycles += branch_penalty(self.beq()),
+            Mnemonic::Bit => self.bit(opcode.mode),
+            Mnemonic::Bmi => self.cycles += branch_penalty(self.bmi()),
+            Mnemonic::Bne => self.cycles += branch_penalty(self.bne()),
+            Mnemonic::Bpl => self.cycles += branch_penalty(self.bpl()),
+            Mnemonic::Brk => self.brk(),
+            Mnemonic::Bvc => self.cycles += branch_penalty(self.bvc()),
+            Mnemonic::Bvs => self.cycles += branch_penalty(self.bvs()),
+            Mnemonic::Clc => self.clc(),
+            Mnemonic::Cld => self.cld(),
+            Mnemonic::Cli => self.cli(),
+            Mnemonic::Clv => self.clv(),
+            Mnemonic::Cmp => self.compare(opcode.mode, self.register_a),
+            Mnemonic::Cpx => self.compare(opcode.mode, self.register_x),
+            Mnemonic::Cpy => self.compare(opcode.mode, self.register_y),
+            Mnemonic::Dec => self.dec(opcode.mode),
+            Mnemonic::Dex => self.dex(opcode.mode),
+            Mnemonic::Dey => self.dey(opcode.mode),
+            Mnemonic::Eor => self.eor(opcode.mode),
+            Mnemonic::Inc => self.inc(opcode.mode),
+            Mnemonic::Inx => self.inx(),
+            Mnemonic::Iny => self.iny(),
+            Mnemonic::Jmp => self.jmp(opcode.mode),
+            Mnemonic::Jsr => self.jsr(),
+            Mnemonic::Lda => self.lda(opcode.mode),
+            Mnemonic::Ldx => self.ldx(opcode.mode),
+            Mnemonic::Ldy => self.ldy(opcode.mode),
+            Mnemonic::Lsr => self.lsr(opcode.mode),
+            Mnemonic::Nop => self.nop(opcode.mode),
+            Mnemonic::Ora => self.ora(opcode.mode),
+            Mnemonic::Pha => self.pha(opcode.mode),
+            Mnemonic::Php => self.php(opcode.mode),
+            Mnemonic::Pla => self.pla(opcode.mode),
+            Mnemonic::Plp => self.plp(opcode.mode),
+            Mnemonic::Rol => self.rol(opcode.mode),
+            Mnemonic::Ror => self.ror(opcode.mode),
+            Mnemonic::Rti => self.rti(),
+            Mnemonic::Rts => self.rts(),
+            Mnemonic::Sbc => self.sbc(opcode.mode),
+            Mnemonic::Sec => self.sec(),
+            Mnemonic::Sed => self.sed(),
+            Mnemonic::Sei => self.sei(),
+            Mnemonic::Sta => self.sta(opcode.mode),
+            Mnemonic::Stx => self.stx(opcode.mode),
+            Mnemonic::Sty => self.sty(opcode.mode),
+            Mnemonic::Tax => self.tax(),
+            Mnemonic::Tay => self.tay(),
+            Mnemonic::Tsx => self.tsx(),
+            Mnemonic::Txa => self.txa(),
+            Mnemonic::Txs => self.txs(),
+            Mnemonic::Tya => self.tya(),
+            Mnemonic::Dcp => self.dcp(opcode.mode),
+            Mnemonic::Isb => self.isb(opcode.mode),
+            Mnemonic::Lax => self.lax(opcode.mode),
+            Mnemonic::Rla => self.rla(opcode.mode),
+            Mnemonic::Rra => self.rra(opcode.mode),
+            Mnemonic::Sax => self.sax(opcode.mode),
+            Mnemonic::Slo => self.slo(opcode.mode),
+            Mnemonic::Sre => self.sre(opcode.mode),
+        }
+    }
+
+    /// Apply the [`IllegalOpcodePolicy`] to the unknown opcode just fetched from `pc`.
+    fn illegal_opcode(&mut self, err: UnknownOpcode, pc: u16) -> Result<Executed, UnknownOpcode> {
+        match self.illegal_opcode_policy {
+            IllegalOpcodePolicy::Error => {
+                self.program_counter = pc;
+                Err(UnknownOpcode {
+                    pc: Some(pc),
+                    ..err
+                })
+            }
+            IllegalOpcodePolicy::Halt => Ok(Executed::Halted(None)),
+            IllegalOpcodePolicy::Nop => {
+                let nop = OpCode::new(err.code, Mnemonic::Nop, 1, 2, AddressingMode::Implicit);
+                self.cycles += u64::from(nop.cycles);
+                Ok(Executed::Ran(nop))
+            }
+        }
+    }
+
+    /// Step, but run a JSR's whole subroutine as one step by stopping when it returns: back at the
+    /// return address with the stack pointer where it was before the JSR.
+    ///
+    /// Returns the stepped-over opcode, or `None` if the CPU halted or `max_instructions` ran out
+    /// before getting back.
+    pub fn step_over(&mut self, max_instructions: usize) -> Option<OpCode> {
+        let return_addr = self.next_pc();
+        let stack_ptr = self.stack_ptr;
+        let opcode = self.step().executed()?;
+
+        if opcode.mnemonic == Mnemonic::Jsr {
+            let mut remaining = max_instructions.checked_sub(1)?;
+            while (self.program_counter, self.stack_ptr) != (return_addr, stack_ptr) {
+                remaining = remaining.checked_sub(1)?;
+                self.step().executed()?;
             }
+        }
+
+        Some(opcode)
+    }
+
+    /// Run until an instruction writes to `addr`, stopping right after that instruction.
+    pub fn run_until_write(&mut self, addr: u16, max_instructions: usize) -> RunStop {
+        self.write_watch = Some((addr, None));
+
+        let mut stop = RunStop::InstructionLimit;
+        for _ in 0..max_instructions {
+            let pc = self.program_counter;
+            if self.step().halted {
+                stop = RunStop::Halted;
+                break;
+            }
+            if let Some((_, Some(value))) = self.write_watch {
+                stop = RunStop::Write { pc, value };
+                break;
+            }
+        }
 
-            if program_counter_state == self.program_counter {
-                // minus one since we inc when mem_read @ start of fn
-                self.program_counter += u16::from(opcode.len - 1);
+        self.write_watch = None;
+        stop
+    }
+
+    /// Run a test ROM using the blargg convention: the ROM writes `magic` right after
+    /// `status_addr`, keeps the status at 0x80 while running, then stores its result code.
+    ///
+    /// Returns the result code, or `None` if the CPU halted or `max_instructions` ran out first.
+    pub fn run_until_test_complete(
+        &mut self,
+        status_addr: u16,
+        magic: &[u8],
+        max_instructions: usize,
+    ) -> Option<u8> {
+        const RUNNING: u8 = 0x80;
+
+        for _ in 0..max_instructions {
+            self.step().executed()?;
+
+            let status = self.mem_read(status_addr);
+            let signed = (status_addr.wrapping_add(1)..)
+                .zip(magic)
+                .all(|(addr, &byte)| self.mem_read(addr) == byte);
+            if signed && status != RUNNING {
+                return Some(status);
             }
+        }
+        None
+    }
 
-            callback(self);
+    /// Run until the current subroutine returns to its caller: the first RTS/RTI that pulls the
+    /// stack pointer above where it was when `step_out` started. Subroutines it calls and
+    /// interrupts it takes (BRK, NMI, IRQ) return to that level or below, so they do not count.
+    ///
+    /// Returns that RTS/RTI, or `None` if the CPU halted or `max_instructions` ran out first.
+    pub fn step_out(&mut self, max_instructions: usize) -> Option<OpCode> {
+        let stack_ptr = self.stack_ptr;
+
+        for _ in 0..max_instructions {
+            let opcode = self.step().executed()?;
+            if matches!(opcode.mnemonic, Mnemonic::Rts | Mnemonic::Rti)
+                && self.stack_ptr > stack_ptr
+            {
+                return Some(opcode);
+            }
         }
+        None
+    }
+
+    /// Address of the instruction after the one at the program counter, i.e. `PC + len`.
+    ///
+    /// Only decodes through [`Memory::peek`], nothing is executed and no CPU state changes. A byte
+    /// outside the opcode table counts as one byte long.
+    #[must_use]
+    pub fn next_pc(&self) -> u16 {
+        let len = opcode_array::lookup(self.peek(self.program_counter)).map_or(1, |op| op.len);
+        self.program_counter.wrapping_add(len.into())
     }
 
     pub fn run(&mut self) {
         self.run_with_callback(|_| {});
     }
 
-    pub fn load(&mut self, program: &[u8]) {
+    /// [`CPU::run`] that stops on an unknown opcode and returns it instead of panicking. The
+    /// program counter is left on the offending byte.
+    pub fn try_run(&mut self) -> Result<(), UnknownOpcode> {
+        while self.try_step()?.executed().is_some() {}
+        Ok(())
+    }
+
+    pub fn load(&mut self, program: impl AsRef<[u8]>) -> Result<(), LoadError> {
+        let program = program.as_ref();
         let start: usize = PRG_ROM_START_ADDR.into();
-        self.memory[start..(start + program.len())].copy_from_slice(program);
-        self.mem_write_u16(PRG_ROM_EXEC_ADDR, PRG_ROM_START_ADDR);
+        let end = start + program.len();
+        if end > PRG_ROM_EXEC_ADDR.into() {
+            return Err(LoadError::OverlapsResetVector { end });
+        }
+
+        for (addr, &data) in (PRG_ROM_START_ADDR..).zip(program) {
+            self.mem_write(addr, data);
+        }
+        self.loaded = Some(start..end);
+        self.set_reset_vector(PRG_ROM_START_ADDR);
+        Ok(())
     }
 
-    pub fn load_and_run(&mut self, program: &[u8]) {
-        self.load(program);
+    /// Load, reset and run until the CPU halts. A bare program ending in BRK needs
+    /// [`BrkPolicy::Halt`] to stop there.
+    pub fn load_and_run(&mut self, program: impl AsRef<[u8]>) -> Result<(), LoadError> {
+        self.load(program)?;
         self.reset();
+
+        let loaded = self.loaded.clone().unwrap_or_default();
+        if !loaded.contains(&usize::from(self.program_counter)) {
+            return Err(LoadError::ResetVectorOutOfRange {
+                vector: self.program_counter,
+            });
+        }
+
         self.run();
+        Ok(())
+    }
+
+    /// Cold start: SP begins at 0x00 so the reset sequence leaves it at 0xFD.
+    pub fn power_on(&mut self) {
+        self.stack_ptr = STACK_POWER_ON;
+        self.reset();
     }
 
+    /// Warm reset. Like the hardware, this only decrements SP three times (the suppressed pushes
+    /// of the interrupt sequence), so it lands on 0xFD only when starting from power-on.
     pub fn reset(&mut self) {
         self.register_a = 0;
         self.register_x = 0;
         self.register_y = 0;
-        self.stack_ptr = STACK_RESET;
+        self.stack_ptr = self.stack_ptr.wrapping_sub(RESET_STACK_DECREMENTS);
         self.status = CpuFlags::default();
-        // memory: [0; 0xFFFF],
+        // memory: [0; MEMORY_SIZE],
+        if self.stack_depth.is_some() {
+            self.stack_depth = Some(StackDepth::default());
+        }
+        self.uninitialized_fetch = None;
 
         self.program_counter = self.mem_read_u16(PRG_ROM_EXEC_ADDR);
     }
 
     // Stack impl
     pub fn stack_pop(&mut self) -> u8 {
+        if cfg!(debug_assertions) && self.stack_ptr == u8::MAX {
+            self.stack_errors.push(StackError::Underflow {
+                pc: self.program_counter,
+            });
+        }
         self.stack_ptr = self.stack_ptr.wrapping_add(1);
         self.mem_read(STACK_START + u16::from(self.stack_ptr))
     }
 
     pub fn stack_push(&mut self, data: u8) {
+        if cfg!(debug_assertions) && self.stack_ptr == u8::MIN {
+            self.stack_errors.push(StackError::Overflow {
+                pc: self.program_counter,
+            });
+        }
         self.mem_write(STACK_START + u16::from(self.stack_ptr), data);
         self.stack_ptr = self.stack_ptr.wrapping_sub(1);
     }
 
+    /// Live stack contents from the top (last pushed byte) down to 0x01FF.
+    ///
+    /// Copied out because the backing memory is only reachable through [`Memory::mem_read`].
+    #[must_use]
+    pub fn stack_slice(&self) -> Vec<u8> {
+        let top = STACK_START + u16::from(self.stack_ptr) + 1;
+        (top..=STACK_MEMORY_END)
+            .map(|addr| self.mem_read(addr))
+            .collect()
+    }
+
+    /// Byte `offset` entries below the top of the stack, `None` past the bottom of the stack.
+    #[must_use]
+    pub fn stack_peek(&self, offset: u8) -> Option<u8> {
+        let addr = STACK_START + u16::from(self.stack_ptr) + 1 + u16::from(offset);
+        (addr <= STACK_MEMORY_END).then(|| self.mem_read(addr))
+    }
+
+    /// Stack wraps recorded since the last call. Always empty in release builds, where the stack
+    /// wraps silently like the hardware does.
+    pub fn take_stack_errors(&mut self) -> Vec<StackError> {
+        std::mem::take(&mut self.stack_errors)
+    }
+
+    /// The discarded reads RTS/RTI/PLA/PLP make before pulling: the byte after the opcode, then
+    /// the stack slot SP points at (before it is incremented).
+    fn stack_pull_dummy_reads(&self) {
+        let _ = self.mem_read(self.program_counter);
+        let _ = self.mem_read(STACK_START + u16::from(self.stack_ptr));
+    }
+
     pub fn stack_pop_u16(&mut self) -> u16 {
         let low = self.stack_pop();
         let high = self.stack_pop();
@@ -222,48 +873,58 @@ impl CPU {
 
     // utility fn
     fn get_operand_address(&self, mode: AddressingMode) -> u16 {
+        self.operand_address_at(mode, self.program_counter, |addr| self.mem_read(addr))
+    }
+
+    /// Effective address of `mode` for an instruction whose operand starts at `operand`, reading
+    /// memory through `read` so tracing can resolve addresses with [`Memory::peek`].
+    fn operand_address_at(
+        &self,
+        mode: AddressingMode,
+        operand: u16,
+        read: impl Fn(u16) -> u8,
+    ) -> u16 {
+        let read_u16 = |pos: u16| u16::from_le_bytes([read(pos), read(pos.wrapping_add(1))]);
+        let read_u16_zp_wrap =
+            |pos: u8| u16::from_le_bytes([read(pos.into()), read(pos.wrapping_add(1).into())]);
+
         match mode {
-            AddressingMode::Immediate => self.program_counter,
+            AddressingMode::Immediate => operand,
 
-            AddressingMode::ZeroPage => self.mem_read(self.program_counter).into(),
+            AddressingMode::ZeroPage => read(operand).into(),
 
-            AddressingMode::Absolute => self.mem_read_u16(self.program_counter),
+            AddressingMode::Absolute => read_u16(operand),
 
             AddressingMode::ZeroPage_X => {
-                let pos = self.mem_read(self.program_counter);
+                let pos = read(operand);
                 pos.wrapping_add(self.register_x).into()
             }
 
             AddressingMode::ZeroPage_Y => {
-                let pos = self.mem_read(self.program_counter);
+                let pos = read(operand);
                 pos.wrapping_add(self.register_y).into()
             }
 
             AddressingMode::Absolute_X => {
-                let base = self.mem_read_u16(self.program_counter);
+                let base = read_u16(operand);
                 base.wrapping_add(self.register_x.into())
             }
 
             AddressingMode::Absolute_Y => {
-                let base = self.mem_read_u16(self.program_counter);
+                let base = read_u16(operand);
                 base.wrapping_add(self.register_y.into())
             }
 
             AddressingMode::Indirect_X => {
-                let base = self.mem_read(self.program_counter);
+                let base = read(operand);
 
                 let ptr: u8 = base.wrapping_add(self.register_x);
-                let lo = self.mem_read(ptr.into());
-                let hi = self.mem_read(ptr.wrapping_add(1).into());
-
-                u16::from_le_bytes([lo, hi])
+                read_u16_zp_wrap(ptr)
             }
 
             AddressingMode::Indirect_Y => {
-                let base = self.mem_read(self.program_counter);
-                let lo = self.mem_read(base.into());
-                let hi = self.mem_read(base.wrapping_add(1).into());
-                let deref_base = u16::from_le_bytes([lo, hi]);
+                let base = read(operand);
+                let deref_base = read_u16_zp_wrap(base);
 
                 deref_base.wrapping_add(self.register_y.into())
             }
@@ -275,7 +936,21 @@ impl CPU {
         }
     }
 
-    fn get_memory(&mut self, mode: AddressingMode) -> (u16, u8) {
+    /// Whether indexing put the effective address of `mode` on another page than its base.
+    fn operand_page_crossed(&self, mode: AddressingMode) -> bool {
+        let base = match mode {
+            AddressingMode::Absolute_X | AddressingMode::Absolute_Y => {
+                self.mem_read_u16(self.program_counter)
+            }
+            AddressingMode::Indirect_Y => {
+                self.mem_read_u16_zp_wrap(self.mem_read(self.program_counter))
+            }
+            _ => return false,
+        };
+        page_crossed(base, self.get_operand_address(mode))
+    }
+
+    fn get_memory(&self, mode: AddressingMode) -> (u16, u8) {
         let addr = self.get_operand_address(mode);
         let value = self.mem_read(addr);
 
@@ -314,23 +989,25 @@ impl CPU {
         self.status.set(CpuFlags::CARRY, value & 1 == 1);
     }
 
+    /// Returns `(taken, page_crossed)`, the latter relative to the next instruction's address.
     #[allow(
         clippy::cast_lossless,
         clippy::cast_possible_wrap,
         clippy::cast_sign_loss
     )]
-    fn branch(&mut self, condition: bool) {
-        if condition {
-            let data = self.mem_read(self.program_counter);
-            let data = i8::from_le_bytes([data]);
-            let data = i16::from(data);
-
-            self.program_counter = self
-                .program_counter
-                // program counter increment durring instruction execution
-                .wrapping_add(1)
-                .wrapping_add_signed(data);
+    fn branch(&mut self, condition: bool) -> (bool, bool) {
+        if !condition {
+            return (false, false);
         }
+
+        let data = self.mem_read(self.program_counter);
+        let data = i8::from_le_bytes([data]);
+        let data = i16::from(data);
+
+        // program counter increment durring instruction execution
+        let next = self.program_counter.wrapping_add(1);
+        self.program_counter = next.wrapping_add_signed(data);
+        (true, page_crossed(next, self.program_counter))
     }
 
     fn compare(&mut self, mode: AddressingMode, with: u8) {
@@ -339,13 +1016,26 @@ impl CPU {
         self.update_zero_and_negative_flags(with.wrapping_sub(data));
     }
 
+    /// Carry in/out of the most recent ADC or SBC, `None` until one has executed.
+    #[must_use]
+    pub const fn last_arithmetic_carry(&self) -> Option<ArithmeticCarry> {
+        self.last_carry
+    }
+
     fn add_to_accumulator(&mut self, data: u8) {
+        let carry_in = self.status.contains(CpuFlags::CARRY);
+
         // convert u8 to u16 for easy carry bit logic
         let sum: u16 = u16::from(self.register_a)
             + u16::from(data) // add accmulator and value together; no worry if overflow because both are u8s
-            + u16::from(self.status.contains(CpuFlags::CARRY)); // Add 1 if carry bit was set
+            + u16::from(carry_in); // Add 1 if carry bit was set
 
-        self.status.set(CpuFlags::CARRY, sum > u8::MAX.into());
+        let carry_out = sum > u8::MAX.into();
+        self.status.set(CpuFlags::CARRY, carry_out);
+        self.last_carry = Some(ArithmeticCarry {
+            carry_in,
+            carry_out,
+        });
 
         // Truncate sum
         // let result: u8 = sum as u8;
@@ -361,58 +1051,112 @@ impl CPU {
     }
 }
 
+impl CPU<Bus> {
+    /// Controller 1 button states recorded since [`Joypad::start_recording`], one per frame.
+    ///
+    /// [`Joypad::start_recording`]: crate::joypad::Joypad::start_recording
+    pub fn take_input_recording(&mut self) -> Vec<JoypadButton> {
+        self.memory.joypad1_mut().take_recording()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{bus::Bus, joypad::JoypadButton};
 
-    #[test]
-    fn test_0xa9_lda_immidiate_load_data() {
+    /// The test programs end in BRK and expect the run loop to stop there.
+    fn test_cpu() -> CPU {
         let mut cpu = CPU::default();
-        cpu.load_and_run(&[0xA9, 0x05, 0x00]);
+        cpu.set_brk_policy(BrkPolicy::Halt);
+        cpu
+    }
+
+    /// Power on with `program` loaded, then check one [`CPU::trace`] line per step against
+    /// `expected`, failing at the first divergence with the line before it for context.
+    fn assert_trace_matches(program: &[u8], expected: &[&str]) -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load(program)?;
+        cpu.power_on();
+
+        let mut previous = "(power on)";
+        for (number, &line) in (1..).zip(expected) {
+            let actual = cpu.trace();
+            assert!(
+                actual == line,
+                "trace diverges at line {number}\n   after: {previous}\nexpected: {line}\n  actual: {actual}"
+            );
+            previous = line;
+            cpu.step();
+        }
+        Ok(())
+    }
+
+    /// Cycles spent by the second instruction of `program`, the first one setting it up.
+    fn cycles_of_second_step(program: impl AsRef<[u8]>) -> Result<u64, LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load(program)?;
+        cpu.reset();
+        cpu.step();
+        let before = cpu.cycles();
+        cpu.step();
+        Ok(cpu.cycles() - before)
+    }
+
+    #[test]
+    fn test_0xa9_lda_immidiate_load_data() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load_and_run([0xA9, 0x05, 0x00])?;
         assert_eq!(cpu.register_a, 0x05);
         assert!(cpu.status.bits() & 0b0000_0010 == 0b00);
         assert!(cpu.status.bits() & 0b1000_0000 == 0);
+        Ok(())
     }
 
     #[test]
-    fn test_lda_from_memory() {
-        let mut cpu = CPU::default();
+    fn test_lda_from_memory() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
         cpu.mem_write(0x10, 0x55);
 
-        cpu.load_and_run(&[0xA5, 0x10, 0x00]);
+        cpu.load_and_run([0xA5, 0x10, 0x00])?;
 
         assert_eq!(cpu.register_a, 0x55);
+        Ok(())
     }
 
     #[test]
-    fn test_0xa9_lda_zero_flag() {
-        let mut cpu = CPU::default();
-        cpu.load_and_run(&[0xA9, 0x00, 0x00]);
+    fn test_0xa9_lda_zero_flag() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load_and_run([0xA9, 0x00, 0x00])?;
         assert!(cpu.status.bits() & 0b0000_0010 == 0b10);
+        Ok(())
     }
 
     #[test]
-    fn test_0xaa_tax_move_a_to_x() {
-        let mut cpu = CPU::default();
-        cpu.load_and_run(&[0xA9, 0x0A, 0xAA, 0x00]);
+    fn test_0xaa_tax_move_a_to_x() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load_and_run([0xA9, 0x0A, 0xAA, 0x00])?;
 
         assert_eq!(cpu.register_x, 10);
+        Ok(())
     }
 
     #[test]
-    fn test_5_ops_working_together() {
-        let mut cpu = CPU::default();
-        cpu.load_and_run(&[0xA9, 0xC0, 0xAA, 0xE8, 0x00]);
+    fn test_5_ops_working_together() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load_and_run([0xA9, 0xC0, 0xAA, 0xE8, 0x00])?;
 
         assert_eq!(cpu.register_x, 0xC1);
+        Ok(())
     }
 
     #[test]
-    fn test_inx_overflow() {
-        let mut cpu = CPU::default();
-        cpu.load_and_run(&[0xA9, 0xFF, 0xAA, 0xE8, 0xE8, 0x00]);
+    fn test_inx_overflow() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load_and_run([0xA9, 0xFF, 0xAA, 0xE8, 0xE8, 0x00])?;
 
         assert_eq!(cpu.register_x, 1);
+        Ok(())
     }
 
     #[test]
@@ -422,4 +1166,1517 @@ mod tests {
         cpu.msb_to_carry_flag(0b1000_0000);
         assert!(cpu.status.contains(CpuFlags::CARRY));
     }
+
+    #[test]
+    fn test_max_stack_depth_nested_jsr() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        assert_eq!(cpu.max_stack_depth(), None);
+        cpu.track_stack_depth(true);
+
+        cpu.load_and_run([
+            0x20, 0x04, 0x06, // 0x0600: JSR $0604
+            0x00, //             0x0603: BRK
+            0x20, 0x08, 0x06, // 0x0604: JSR $0608
+            0x60, //             0x0607: RTS
+            0x20, 0x0C, 0x06, // 0x0608: JSR $060C
+            0x60, //             0x060B: RTS
+            0x60, //             0x060C: RTS
+        ])?;
+
+        assert_eq!(cpu.max_stack_depth(), Some(3));
+        assert_eq!(cpu.stack_depth(), Some(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_status_string() {
+        let flags = CpuFlags::INTERUPT_DISABLE | CpuFlags::ZERO;
+        assert_eq!(flags.as_status_string(), "nv--dIZc");
+
+        let flags = CpuFlags::NEGATIV | CpuFlags::BREAK | CpuFlags::BREAK2 | CpuFlags::CARRY;
+        assert_eq!(flags.as_status_string(), "Nv--dizC");
+    }
+
+    #[test]
+    fn test_call_hook_reports_nested_calls() -> Result<(), LoadError> {
+        use super::call_graph::CallEvent;
+        use std::{cell::RefCell, rc::Rc};
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut cpu = test_cpu();
+        let sink = Rc::clone(&events);
+        cpu.set_call_hook(move |event| sink.borrow_mut().push(event));
+
+        cpu.load_and_run([
+            0x20, 0x04, 0x06, // 0x0600: JSR $0604
+            0x00, //             0x0603: BRK
+            0x20, 0x08, 0x06, // 0x0604: JSR $0608
+            0x60, //             0x0607: RTS
+            0x60, //             0x0608: RTS
+        ])?;
+
+        assert_eq!(
+            *events.borrow(),
+            [
+                CallEvent::Call {
+                    caller: 0x0600,
+                    target: 0x0604
+                },
+                CallEvent::Call {
+                    caller: 0x0604,
+                    target: 0x0608
+                },
+                CallEvent::Return { target: 0x0607 },
+                CallEvent::Return { target: 0x0603 },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_jsr_rts_resumes_after_call_exactly_once() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load_and_run([
+            0x20, 0x06, 0x06, // 0x0600: JSR $0606
+            0xE8, //             0x0603: INX
+            0x00, //             0x0604: BRK
+            0x00, //             0x0605: padding
+            0xC8, //             0x0606: INY
+            0x60, //             0x0607: RTS
+        ])?;
+
+        assert_eq!(cpu.register_y, 1);
+        assert_eq!(cpu.register_x, 1);
+        assert_eq!(cpu.stack_ptr, STACK_RESET);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_at_0xffff_wraps_to_zero_page() {
+        let mut cpu = test_cpu();
+        cpu.mem_write(0xFFFF, 0xA9); // LDA #$42, operand wraps to 0x0000
+        cpu.mem_write(0x0000, 0x42);
+        cpu.mem_write(0x0001, 0x00); // BRK
+        cpu.mem_write_u16(PRG_ROM_EXEC_ADDR, 0xFFFF);
+
+        cpu.reset();
+        cpu.run();
+
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.program_counter, 0x0002);
+    }
+
+    #[test]
+    fn test_trap_on_uninitialized_fetch() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.trap_uninitialized_fetch(true);
+        cpu.load([0xE8, 0x00])?;
+        cpu.mem_write_u16(PRG_ROM_EXEC_ADDR, 0x8000);
+
+        cpu.reset();
+        cpu.run();
+
+        assert_eq!(cpu.uninitialized_fetch(), Some(0x8000));
+        assert_eq!(cpu.program_counter, 0x8000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flat_memory_has_no_io() {
+        let mut cpu = CPU::new(FlatMemory::default());
+        cpu.mem_write(0x2000, 0x42);
+        cpu.mem_write(0x0800, 0x24);
+
+        assert_eq!(cpu.mem_read(0x2000), 0x42);
+        assert_eq!(cpu.mem_read(0x2008), 0x00);
+        assert_eq!(cpu.mem_read(0x0000), 0x00);
+        assert_eq!(cpu.memory().mem_read(0x0800), 0x24);
+    }
+
+    #[test]
+    fn test_load_rejects_program_overlapping_reset_vector() {
+        let mut cpu = CPU::default();
+        let fits = vec![0xEA; usize::from(PRG_ROM_EXEC_ADDR - PRG_ROM_START_ADDR)];
+        assert_eq!(cpu.load(&fits), Ok(()));
+
+        let too_long = vec![0xEA; fits.len() + 1];
+        assert_eq!(
+            cpu.load(&too_long),
+            Err(LoadError::OverlapsResetVector { end: 0xFFFD })
+        );
+        assert_eq!(cpu.mem_read_u16(PRG_ROM_EXEC_ADDR), PRG_ROM_START_ADDR);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_stack_underflow_is_recorded() {
+        let mut cpu = CPU::default();
+        cpu.mem_write(0x0100, 0x42);
+        cpu.stack_ptr = 0xFF;
+
+        assert_eq!(cpu.stack_pop(), 0x42);
+        assert_eq!(cpu.stack_ptr, 0x00);
+        assert_eq!(
+            cpu.take_stack_errors(),
+            [StackError::Underflow { pc: 0x0000 }]
+        );
+        assert!(cpu.take_stack_errors().is_empty());
+    }
+
+    #[test]
+    fn test_adc_reports_carry_in_and_out() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        assert_eq!(cpu.last_arithmetic_carry(), None);
+
+        // SEC; LDA #$FF; ADC #$01; BRK
+        cpu.load_and_run([0x38, 0xA9, 0xFF, 0x69, 0x01, 0x00])?;
+
+        assert_eq!(cpu.register_a, 0x01);
+        assert_eq!(
+            cpu.last_arithmetic_carry(),
+            Some(ArithmeticCarry {
+                carry_in: true,
+                carry_out: true
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_callback_sees_executed_opcodes() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([0xA9, 0x01, 0xAA, 0xE8, 0x00])?;
+        cpu.reset();
+
+        let mut executed = Vec::new();
+        cpu.run_with_opcode_callback(|_, opcode| executed.push(opcode.mnemonic));
+
+        // BRK stops the loop before the callback runs
+        assert_eq!(executed, [Mnemonic::Lda, Mnemonic::Tax, Mnemonic::Inx]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_page_pointer_wraps() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.mem_write_u16_zp_wrap(0xFF, 0x0234);
+        assert_eq!(cpu.mem_read(0x00FF), 0x34);
+        assert_eq!(cpu.mem_read(0x0000), 0x02);
+        assert_eq!(cpu.mem_read_u16_zp_wrap(0xFF), 0x0234);
+        cpu.mem_write(0x0234, 0x5A);
+
+        // LDA ($FF),Y with Y = 0
+        cpu.load_and_run([0xB1, 0xFF, 0x00])?;
+        assert_eq!(cpu.register_a, 0x5A);
+        Ok(())
+    }
+
+    #[test]
+    fn test_every_opcode_mode_is_handled() {
+        let unhandled: Vec<&OpCode> = OpCode::table()
+            .iter()
+            .filter(|op| !handles_mode(op.mnemonic, op.mode))
+            .collect();
+
+        assert!(unhandled.is_empty(), "{unhandled:#?}");
+    }
+
+    #[test]
+    fn test_asl_accumulator() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load_and_run([0xA9, 0x81, 0x0A, 0x00])?;
+
+        assert_eq!(cpu.register_a, 0x02);
+        assert!(cpu.status.contains(CpuFlags::CARRY));
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::field_reassign_with_default)]
+    fn test_add_to_accumulator_carry_boundary() {
+        let mut cpu = CPU::default();
+
+        // 0xFF + 0x01 + 0 = 0x100
+        cpu.register_a = 0xFF;
+        cpu.status.remove(CpuFlags::CARRY);
+        cpu.add_to_accumulator(0x01);
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.status.contains(CpuFlags::CARRY));
+
+        // 0xFE + 0x01 + 0 = 0x0FF
+        cpu.register_a = 0xFE;
+        cpu.status.remove(CpuFlags::CARRY);
+        cpu.add_to_accumulator(0x01);
+        assert_eq!(cpu.register_a, 0xFF);
+        assert!(!cpu.status.contains(CpuFlags::CARRY));
+
+        // 0xFF + 0x00 + 1 = 0x100
+        cpu.register_a = 0xFF;
+        cpu.status.insert(CpuFlags::CARRY);
+        cpu.add_to_accumulator(0x00);
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.status.contains(CpuFlags::CARRY));
+    }
+
+    #[test]
+    fn test_stack_slice_is_top_first() {
+        let mut cpu = CPU::default();
+        cpu.power_on();
+        let bottom = cpu.stack_slice();
+        cpu.stack_push(0x11);
+        cpu.stack_push(0x22);
+        cpu.stack_push(0x33);
+
+        let stack = cpu.stack_slice();
+        assert_eq!(stack[..3], [0x33, 0x22, 0x11]);
+        assert_eq!(stack[3..], bottom);
+        assert_eq!(cpu.stack_peek(0), Some(0x33));
+        assert_eq!(cpu.stack_peek(2), Some(0x11));
+        assert_eq!(cpu.stack_peek(0xFF), None);
+    }
+
+    #[test]
+    fn test_next_pc_skips_whole_instruction() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([0xAD, 0x00, 0x02, 0x00])?; // LDA $0200
+        cpu.reset();
+
+        assert_eq!(cpu.next_pc(), 0x0603);
+        assert_eq!(cpu.program_counter, 0x0600);
+        assert_eq!(cpu.register_a, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_take_input_recording_matches_injected_input() {
+        let injected = [
+            JoypadButton::START,
+            JoypadButton::empty(),
+            JoypadButton::RIGHT | JoypadButton::BUTTON_B,
+        ];
+        let mut cpu = CPU::new(Bus::default());
+        let joypad = cpu.memory_mut().joypad1_mut();
+        joypad.start_recording();
+        for buttons in injected {
+            for button in JoypadButton::all() {
+                joypad.set_button(button, buttons.contains(button));
+            }
+            joypad.next_frame();
+        }
+
+        assert_eq!(cpu.take_input_recording(), injected);
+        assert!(cpu.take_input_recording().is_empty());
+    }
+
+    #[test]
+    fn test_next_pc_does_not_touch_the_bus() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.memory_mut()
+            .joypad1_mut()
+            .set_button(JoypadButton::BUTTON_A, true);
+        cpu.program_counter = 0x4016;
+
+        // Button A reads as 0x01, ORA ($nn,X)
+        assert_eq!(cpu.next_pc(), 0x4018);
+        assert_eq!(cpu.next_pc(), 0x4018);
+        // Still on button A: peeking did not shift the controller.
+        assert_eq!(cpu.mem_read(0x4016) & 1, 1);
+    }
+
+    #[test]
+    fn test_step_over_unknown_opcode() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([0x02, 0xE8, 0x00])?; // (illegal); INX; BRK
+        cpu.reset();
+        assert_eq!(cpu.next_pc(), 0x0601);
+
+        cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Nop);
+        assert_eq!(cpu.step_over(100).map(|op| op.code), Some(0x02));
+        assert_eq!(cpu.program_counter, 0x0601);
+
+        cpu.reset();
+        cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Halt);
+        assert_eq!(cpu.step_over(100), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_over_jsr_runs_whole_subroutine() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([
+            0x20, 0x05, 0x06, // 0x0600: JSR $0605
+            0xE8, //             0x0603: INX
+            0x00, //             0x0604: BRK
+            0xC8, //             0x0605: INY
+            0xC8, //             0x0606: INY
+            0x60, //             0x0607: RTS
+        ])?;
+        cpu.reset();
+
+        let stepped = cpu.step_over(100).map(|op| op.mnemonic);
+        assert_eq!(stepped, Some(Mnemonic::Jsr));
+        assert_eq!(cpu.program_counter, 0x0603);
+        assert_eq!(cpu.register_y, 2);
+        assert_eq!(cpu.register_x, 0);
+
+        let stepped = cpu.step_over(100).map(|op| op.mnemonic);
+        assert_eq!(stepped, Some(Mnemonic::Inx));
+        assert_eq!(cpu.register_x, 1);
+        assert_eq!(cpu.step_over(100), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_out_returns_to_caller() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([
+            0x20, 0x05, 0x06, // 0x0600: JSR $0605
+            0xE8, //             0x0603: INX
+            0x00, //             0x0604: BRK
+            0xC8, //             0x0605: INY
+            0x20, 0x0B, 0x06, // 0x0606: JSR $060B
+            0xC8, //             0x0609: INY
+            0x60, //             0x060A: RTS
+            0x60, //             0x060B: RTS
+        ])?;
+        cpu.reset();
+
+        cpu.step(); // JSR
+        cpu.step(); // INY
+        let returned = cpu.step_out(100).map(|op| op.mnemonic);
+
+        assert_eq!(returned, Some(Mnemonic::Rts));
+        assert_eq!(cpu.program_counter, 0x0603);
+        assert_eq!(cpu.register_y, 2);
+        assert_eq!(cpu.register_x, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_out_and_over_skip_brk_handler() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([
+            0x20, 0x05, 0x06, // 0x0600: JSR $0605
+            0xE8, //             0x0603: INX
+            0xEA, //             0x0604: NOP
+            0x00, 0xFF, //       0x0605: BRK
+            0xC8, //             0x0607: INY
+            0x60, //             0x0608: RTS
+        ])?;
+        cpu.mem_write_u16(IRQ_VECTOR, 0x0700);
+        cpu.mem_write(0x0700, 0x40); // RTI
+        cpu.reset();
+
+        cpu.step(); // JSR
+        assert_eq!(cpu.step_out(100).map(|op| op.mnemonic), Some(Mnemonic::Rts));
+        assert_eq!(cpu.program_counter, 0x0603);
+        assert_eq!(cpu.register_y, 1);
+
+        cpu.reset();
+        assert_eq!(
+            cpu.step_over(100).map(|op| op.mnemonic),
+            Some(Mnemonic::Jsr)
+        );
+        assert_eq!(cpu.program_counter, 0x0603);
+        assert_eq!(cpu.register_y, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_out_and_over_are_bounded() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([
+            0x20, 0x04, 0x06, // 0x0600: JSR $0604
+            0x00, //             0x0603: BRK
+            0x4C, 0x04, 0x06, // 0x0604: JMP $0604
+        ])?;
+        cpu.reset();
+
+        assert_eq!(cpu.step_over(50), None);
+        assert_eq!(cpu.step_out(50), None);
+        assert_eq!(cpu.program_counter, 0x0604);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_equal_values() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        // LDA #$C0; CMP #$C0
+        cpu.load_and_run([0xA9, 0xC0, 0xC9, 0xC0, 0x00])?;
+
+        assert!(cpu.status.contains(CpuFlags::CARRY));
+        assert!(cpu.status.contains(CpuFlags::ZERO));
+        assert!(!cpu.status.contains(CpuFlags::NEGATIV));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_less_than_uses_wrapped_difference() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        // LDX #$10; CPX #$20 -> 0x10 - 0x20 = 0xF0
+        cpu.load_and_run([0xA2, 0x10, 0xE0, 0x20, 0x00])?;
+        assert!(!cpu.status.contains(CpuFlags::CARRY));
+        assert!(!cpu.status.contains(CpuFlags::ZERO));
+        assert!(cpu.status.contains(CpuFlags::NEGATIV));
+
+        // LDY #$01; CPY #$90 -> 0x01 - 0x90 = 0x71
+        cpu.load_and_run([0xA0, 0x01, 0xC0, 0x90, 0x00])?;
+        assert!(!cpu.status.contains(CpuFlags::CARRY));
+        assert!(!cpu.status.contains(CpuFlags::ZERO));
+        assert!(!cpu.status.contains(CpuFlags::NEGATIV));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rts_dummy_reads_stack() -> Result<(), LoadError> {
+        use std::cell::RefCell;
+
+        /// Watchpoint over `FlatMemory` recording every address read.
+        #[derive(Default)]
+        struct ReadLog {
+            memory: FlatMemory,
+            reads: RefCell<Vec<u16>>,
+        }
+
+        impl Memory for ReadLog {
+            fn mem_read(&self, addr: u16) -> u8 {
+                self.reads.borrow_mut().push(addr);
+                self.memory.mem_read(addr)
+            }
+
+            fn mem_write(&mut self, addr: u16, data: u8) {
+                self.memory.mem_write(addr, data);
+            }
+        }
+
+        let mut cpu = CPU::new(ReadLog::default());
+        cpu.load([
+            0x20, 0x04, 0x06, // 0x0600: JSR $0604
+            0x00, //             0x0603: BRK
+            0x60, //             0x0604: RTS
+        ])?;
+        cpu.reset();
+        cpu.step();
+
+        cpu.memory().reads.borrow_mut().clear();
+        cpu.step();
+
+        let reads = cpu.memory().reads.borrow().clone();
+        let stack_reads: Vec<u16> = reads
+            .iter()
+            .copied()
+            .filter(|addr| (STACK_START..=STACK_MEMORY_END).contains(addr))
+            .collect();
+        // dummy read at SP, then PCL and PCH
+        assert_eq!(stack_reads, [0x01FB, 0x01FC, 0x01FD]);
+        // opcode, dummy operand read, 3 stack reads, dummy read of the return address
+        assert_eq!(reads, [0x0604, 0x0605, 0x01FB, 0x01FC, 0x01FD, 0x0602]);
+        assert_eq!(cpu.program_counter, 0x0603);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_until_write_stops_after_writer() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([
+            0xA9, 0x01, //       0x0600: LDA #$01
+            0x85, 0x10, //       0x0602: STA $10
+            0xA9, 0x42, //       0x0604: LDA #$42
+            0x8D, 0x00, 0x02, // 0x0606: STA $0200
+            0xE8, //             0x0609: INX
+            0x00, //             0x060A: BRK
+        ])?;
+        cpu.reset();
+
+        assert_eq!(
+            cpu.run_until_write(0x0200, 100),
+            RunStop::Write {
+                pc: 0x0606,
+                value: 0x42
+            }
+        );
+        assert_eq!(cpu.program_counter, 0x0609);
+        assert_eq!(cpu.register_x, 0);
+
+        assert_eq!(cpu.run_until_write(0x0200, 100), RunStop::Halted);
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_reverts_to_earlier_state() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([
+            0xA9, 0x10, //       LDA #$10
+            0x85, 0x20, //       STA $20
+            0xA2, 0x05, //       LDX #$05
+            0x48, //             PHA
+            0xE8, //             INX
+            0x8D, 0x20, 0x00, // STA $0020
+            0x00, //             BRK
+        ])?;
+        cpu.reset();
+        cpu.record_history(8);
+
+        for _ in 0..3 {
+            cpu.step();
+        }
+        let registers = |cpu: &CPU| {
+            (
+                cpu.register_a,
+                cpu.register_x,
+                cpu.register_y,
+                cpu.status,
+                cpu.program_counter,
+                cpu.stack_ptr,
+            )
+        };
+        let saved_registers = registers(&cpu);
+        let saved_memory = cpu.memory().clone();
+
+        cpu.step(); // PHA
+        cpu.step(); // INX
+        assert!(cpu.undo());
+        assert!(cpu.undo());
+
+        assert_eq!(registers(&cpu), saved_registers);
+        assert_eq!(cpu.memory(), &saved_memory);
+        assert_eq!(cpu.undo_depth(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_history_is_bounded() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([0xE8, 0xE8, 0xE8, 0xE8, 0x00])?; // INX x4; BRK
+        cpu.reset();
+        cpu.record_history(2);
+
+        for _ in 0..4 {
+            cpu.step();
+        }
+        assert!(cpu.undo());
+        assert!(cpu.undo());
+        assert!(!cpu.undo());
+        assert_eq!(cpu.register_x, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_restores_cycles_and_pending_nmi() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([0xE8, 0xE8, 0x00])?; // INX; INX; BRK
+        cpu.mem_write_u16(NMI_VECTOR, 0x0700);
+        cpu.reset();
+        cpu.record_history(2);
+
+        cpu.step();
+        cpu.request_nmi();
+        cpu.step(); // NMI entry, then the handler's byte
+        assert!(cpu.undo());
+
+        assert_eq!(cpu.cycles(), 2);
+        assert_eq!(cpu.program_counter, 0x0601);
+        cpu.step(); // the NMI is still pending
+        assert_eq!(cpu.stack_peek(1), Some(0x01));
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_on_bus_only_restores_ram() -> Result<(), LoadError> {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.set_brk_policy(BrkPolicy::Halt);
+        cpu.memory_mut().set_strict(true);
+        cpu.load([
+            0xA9, 0x01, //       LDA #$01
+            0x8D, 0x16, 0x40, // STA $4016
+            0x85, 0x10, //       STA $10
+            0x00, //             BRK
+        ])?;
+        cpu.mem_write(0x0010, 0x77);
+        cpu.reset();
+        cpu.record_history(4);
+
+        cpu.step();
+        cpu.step();
+        cpu.step();
+        assert!(cpu.undo());
+        assert!(cpu.undo());
+
+        assert_eq!(cpu.mem_read(0x0010), 0x77);
+        assert!(cpu.memory().joypad1().strobe());
+        assert!(cpu.memory_mut().take_unmapped_accesses().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_repeats_once_loop_settles() -> Result<(), LoadError> {
+        use std::collections::{hash_map::DefaultHasher, HashSet};
+
+        let mut cpu = CPU::default();
+        cpu.load([
+            0xA2, 0x03, //       0x0600: LDX #$03
+            0xCA, //             0x0602: DEX
+            0xD0, 0xFD, //       0x0603: BNE $0602
+            0x4C, 0x05, 0x06, // 0x0605: JMP $0605
+        ])?;
+        cpu.reset();
+
+        let hash = |cpu: &CPU| {
+            let mut hasher = DefaultHasher::new();
+            cpu.hash(&mut hasher);
+            hasher.finish()
+        };
+        let mut seen = HashSet::new();
+        let mut steps = 0;
+        while seen.insert(hash(&cpu)) {
+            cpu.step();
+            steps += 1;
+        }
+
+        // LDX, three DEX/BNE pairs, then the first JMP lands back on itself.
+        assert_eq!(steps, 8);
+        assert_eq!(cpu.program_counter, 0x0605);
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_symbols() {
+        let mut cpu = CPU::default();
+        cpu.set_symbols([(0x0610, "init"), (0x0620, "loop")].into_iter().collect());
+
+        assert_eq!(cpu.symbols().get(0x0610), Some("init"));
+        assert_eq!(cpu.symbols().get(0x0611), None);
+    }
+
+    #[test]
+    fn test_plp_delays_pending_irq_by_one_instruction() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([
+            0xA9, 0x00, // 0x0600: LDA #$00
+            0x48, //       0x0602: PHA
+            0x28, //       0x0603: PLP
+            0xE8, //       0x0604: INX
+            0xE8, //       0x0605: INX
+            0x00, //       0x0606: BRK
+        ])?;
+        cpu.mem_write_u16(IRQ_VECTOR, 0x0700);
+        cpu.mem_write(0x0700, 0xC8); // INY
+        cpu.reset();
+        cpu.set_irq_line(true);
+
+        cpu.step(); // LDA
+        cpu.step(); // PHA
+        cpu.step(); // PLP, polled while I was still set
+        cpu.step(); // INX, polled with I clear
+        assert_eq!(cpu.program_counter, 0x0605);
+        assert_eq!(cpu.register_x, 1);
+
+        cpu.step(); // IRQ entry, then the handler's INY
+        assert_eq!(cpu.program_counter, 0x0701);
+        assert_eq!((cpu.register_x, cpu.register_y), (1, 1));
+        assert!(cpu.status.contains(CpuFlags::INTERUPT_DISABLE));
+        assert_eq!(cpu.stack_peek(1), Some(0x05));
+        assert_eq!(cpu.stack_peek(2), Some(0x06));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_delays_pending_irq_by_one_instruction() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([
+            0x58, // 0x0600: CLI
+            0xE8, // 0x0601: INX
+            0xE8, // 0x0602: INX
+            0x00, // 0x0603: BRK
+        ])?;
+        cpu.mem_write_u16(IRQ_VECTOR, 0x0700);
+        cpu.mem_write(0x0700, 0xC8); // INY
+        cpu.reset();
+        assert!(cpu.status.contains(CpuFlags::INTERUPT_DISABLE));
+        cpu.set_irq_line(true);
+
+        cpu.step(); // CLI, polled while I was still set
+        cpu.step(); // INX, polled with I clear
+        assert_eq!(cpu.program_counter, 0x0602);
+        assert_eq!(cpu.register_x, 1);
+
+        cpu.step(); // IRQ entry, then the handler's INY
+        assert_eq!(cpu.program_counter, 0x0701);
+        assert_eq!((cpu.register_x, cpu.register_y), (1, 1));
+        assert_eq!(cpu.stack_peek(1), Some(0x02));
+        assert_eq!(cpu.stack_peek(2), Some(0x06));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_and_run_takes_vec_and_array() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load_and_run(vec![0xA9, 0x05, 0x00])?;
+        assert_eq!(cpu.register_a, 0x05);
+
+        cpu.load_and_run([0xA2, 0x07, 0x00])?;
+        assert_eq!(cpu.register_x, 0x07);
+        Ok(())
+    }
+
+    #[test]
+    fn test_0x6a_ror_accumulator_rotates_through_carry() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load_and_run([0x38, 0xA9, 0x01, 0x6A, 0x00])?; // SEC; LDA #$01; ROR A
+        assert_eq!(cpu.register_a, 0x80);
+        assert!(cpu.status.contains(CpuFlags::CARRY));
+        assert!(cpu.status.contains(CpuFlags::NEGATIV));
+
+        cpu.load_and_run([0x18, 0xA9, 0x02, 0x6A, 0x00])?; // CLC; LDA #$02; ROR A
+        assert_eq!(cpu.register_a, 0x01);
+        assert!(!cpu.status.contains(CpuFlags::CARRY));
+        assert!(!cpu.status.contains(CpuFlags::NEGATIV));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_decrements_stack_pointer_by_three() {
+        let mut cpu = CPU::default();
+        cpu.power_on();
+        assert_eq!(cpu.stack_ptr, 0xFD);
+
+        cpu.reset();
+        assert_eq!(cpu.stack_ptr, 0xFA);
+
+        cpu.stack_ptr = 0x01;
+        cpu.reset();
+        assert_eq!(cpu.stack_ptr, 0xFE);
+    }
+
+    #[test]
+    fn test_reset_clears_carry_and_disables_interrupts() {
+        let mut cpu = CPU {
+            status: CpuFlags::all(),
+            ..CPU::default()
+        };
+        cpu.reset();
+
+        assert!(!cpu.status.contains(CpuFlags::CARRY));
+        assert!(cpu.status.contains(CpuFlags::INTERUPT_DISABLE));
+        assert_eq!(cpu.status, CpuFlags::BREAK2 | CpuFlags::INTERUPT_DISABLE);
+    }
+
+    #[test]
+    fn test_php_pushes_break_flags() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([0x08, 0x68, 0x00])?; // PHP; PLA; BRK
+        cpu.reset();
+        cpu.status.remove(CpuFlags::BREAK | CpuFlags::BREAK2);
+        cpu.run();
+
+        let pushed = CpuFlags::from_bits_truncate(cpu.register_a);
+        assert!(pushed.contains(CpuFlags::BREAK | CpuFlags::BREAK2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plp_ignores_break_flags() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([0xA9, 0x10, 0x48, 0x28, 0x00])?; // LDA #BREAK; PHA; PLP; BRK
+        cpu.reset();
+        cpu.run();
+
+        assert!(!cpu.status.contains(CpuFlags::BREAK));
+        assert!(cpu.status.contains(CpuFlags::BREAK2));
+        Ok(())
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_decimal_adc() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([0xF8, 0x18, 0xA9, 0x09, 0x69, 0x01, 0x00])?; // SED; CLC; LDA #$09; ADC #$01; BRK
+        cpu.reset();
+        cpu.run();
+        assert_eq!(cpu.register_a, 0x10);
+        assert!(!cpu.status.contains(CpuFlags::CARRY));
+
+        cpu.load([0xF8, 0x18, 0xA9, 0x99, 0x69, 0x01, 0x00])?; // SED; CLC; LDA #$99; ADC #$01; BRK
+        cpu.reset();
+        cpu.run();
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.status.contains(CpuFlags::CARRY));
+        assert!(cpu.status.contains(CpuFlags::ZERO));
+        Ok(())
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_decimal_sbc() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([0xF8, 0x38, 0xA9, 0x10, 0xE9, 0x01, 0x00])?; // SED; SEC; LDA #$10; SBC #$01; BRK
+        cpu.reset();
+        cpu.run();
+        assert_eq!(cpu.register_a, 0x09);
+        assert!(cpu.status.contains(CpuFlags::CARRY));
+
+        cpu.load([0xF8, 0x38, 0xA9, 0x00, 0xE9, 0x01, 0x00])?; // SED; SEC; LDA #$00; SBC #$01; BRK
+        cpu.reset();
+        cpu.run();
+        assert_eq!(cpu.register_a, 0x99);
+        assert!(!cpu.status.contains(CpuFlags::CARRY));
+        Ok(())
+    }
+
+    #[test]
+    fn test_lax_zero_page() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([0xA7, 0x10, 0x00])?; // LAX $10; BRK
+        cpu.reset();
+        cpu.mem_write(0x10, 0x80);
+        cpu.run();
+
+        assert_eq!(cpu.register_a, 0x80);
+        assert_eq!(cpu.register_x, 0x80);
+        assert!(cpu.status.contains(CpuFlags::NEGATIV));
+        assert!(!cpu.status.contains(CpuFlags::ZERO));
+        assert_eq!(cpu.cycles(), 3 + 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dcp_absolute() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([0xA9, 0x41, 0xCF, 0x00, 0x02, 0x00])?; // LDA #$41; DCP $0200; BRK
+        cpu.reset();
+        cpu.mem_write(0x0200, 0x42);
+        cpu.run();
+
+        assert_eq!(cpu.mem_read(0x0200), 0x41);
+        assert_eq!(cpu.register_a, 0x41);
+        assert!(cpu.status.contains(CpuFlags::ZERO | CpuFlags::CARRY));
+        assert!(!cpu.status.contains(CpuFlags::NEGATIV));
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_reports_opcode_and_cycles() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([0xA9, 0x01, 0x8D, 0x00, 0x02, 0x00])?; // LDA #$01; STA $0200; BRK
+        cpu.reset();
+
+        let steps: Vec<_> = (0..3)
+            .map(|_| {
+                let step = cpu.step();
+                (
+                    step.pc_before,
+                    step.opcode.map(|op| op.mnemonic),
+                    step.cycles,
+                    step.halted,
+                )
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                (0x0600, Some(Mnemonic::Lda), 2, false),
+                (0x0602, Some(Mnemonic::Sta), 4, false),
+                (0x0605, Some(Mnemonic::Brk), 7, true),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_matches_nestest_log() {
+        let mut cpu = CPU::default();
+        cpu.power_on();
+        for (addr, bytes) in [
+            (0xC000, &[0x4C, 0xF5, 0xC5][..]), // JMP $C5F5
+            (
+                0xC5F5,
+                &[
+                    0xA2, 0x00, 0x86, 0x00, 0x86, 0x10, 0x86, 0x11, 0x20, 0x2D, 0xC7,
+                ],
+            ),
+            (0xC72D, &[0xEA, 0x38, 0xB0, 0x04]), // NOP; SEC; BCS $C735
+        ] {
+            for (addr, &data) in (addr..).zip(bytes) {
+                cpu.mem_write(addr, data);
+            }
+        }
+        cpu.program_counter = 0xC000;
+
+        let expected = [
+            "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD",
+            "C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD",
+            "C5F7  86 00     STX $00 = 00                    A:00 X:00 Y:00 P:26 SP:FD",
+            "C5F9  86 10     STX $10 = 00                    A:00 X:00 Y:00 P:26 SP:FD",
+            "C5FB  86 11     STX $11 = 00                    A:00 X:00 Y:00 P:26 SP:FD",
+            "C5FD  20 2D C7  JSR $C72D                       A:00 X:00 Y:00 P:26 SP:FD",
+            "C72D  EA        NOP                             A:00 X:00 Y:00 P:26 SP:FB",
+            "C72E  38        SEC                             A:00 X:00 Y:00 P:26 SP:FB",
+            "C72F  B0 04     BCS $C735                       A:00 X:00 Y:00 P:27 SP:FB",
+        ];
+        for line in expected {
+            assert_eq!(cpu.trace(), line);
+            cpu.step();
+        }
+    }
+
+    #[test]
+    fn test_golden_trace() -> Result<(), LoadError> {
+        assert_trace_matches(
+            &[
+                0xA9, 0x80, // LDA #$80
+                0xAA, //       TAX
+                0xE8, //       INX
+                0x85, 0x10, // STA $10
+                0xC5, 0x10, // CMP $10
+                0xF0, 0x01, // BEQ $060B
+                0x00, //       BRK
+                0x0A, //       ASL A
+            ],
+            &[
+                "0600  A9 80     LDA #$80                        A:00 X:00 Y:00 P:24 SP:FD",
+                "0602  AA        TAX                             A:80 X:00 Y:00 P:A4 SP:FD",
+                "0603  E8        INX                             A:80 X:80 Y:00 P:A4 SP:FD",
+                "0604  85 10     STA $10 = 00                    A:80 X:81 Y:00 P:A4 SP:FD",
+                "0606  C5 10     CMP $10 = 80                    A:80 X:81 Y:00 P:A4 SP:FD",
+                "0608  F0 01     BEQ $060B                       A:80 X:81 Y:00 P:27 SP:FD",
+                "060B  0A        ASL A                           A:80 X:81 Y:00 P:27 SP:FD",
+            ],
+        )
+    }
+
+    #[test]
+    fn test_trace_indexed_and_unofficial_operands() {
+        let mut cpu = CPU::default();
+        cpu.power_on();
+        cpu.program_counter = 0x0064;
+        cpu.mem_write(0x0064, 0x11); // ORA ($33),Y
+        cpu.mem_write(0x0065, 0x33);
+        cpu.mem_write_u16(0x0033, 0x0400);
+        cpu.mem_write(0x0400, 0xAA);
+        assert_eq!(
+            cpu.trace(),
+            "0064  11 33     ORA ($33),Y = 0400 @ 0400 = AA  A:00 X:00 Y:00 P:24 SP:FD"
+        );
+
+        cpu.mem_write(0x0064, 0xA7); // *LAX $33
+        assert_eq!(
+            cpu.trace(),
+            "0064  A7 33    *LAX $33 = 00                    A:00 X:00 Y:00 P:24 SP:FD"
+        );
+
+        cpu.mem_write(0x0064, 0x94); // STY $33,X
+        cpu.register_x = 0x02;
+        cpu.register_y = 0x5A;
+        assert_eq!(
+            cpu.trace(),
+            "0064  94 33     STY $33,X @ 35 = 00             A:00 X:02 Y:5A P:24 SP:FD"
+        );
+        cpu.step();
+        assert_eq!(cpu.mem_read(0x0035), 0x5A);
+    }
+
+    #[test]
+    fn test_disassemble_operand_syntax() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([
+            0xA9, 0x10, // 0x0600: LDA #$10
+            0xB5, 0x10, // 0x0602: LDA $10,X
+            0xB1, 0x20, // 0x0604: LDA ($20),Y
+            0xD0, 0xF8, // 0x0606: BNE $0600
+            0x6C, 0x00, 0x02, // 0x0608: JMP ($0200)
+            0x0A, // 0x060B: ASL A
+            0x02, // 0x060C: (illegal)
+            0xA7, 0x20, // 0x060D: *LAX $20
+            0xEA, // 0x060F: NOP
+        ])?;
+
+        assert_eq!(cpu.disassemble(0x0600), ("LDA #$10".to_owned(), 2));
+        assert_eq!(cpu.disassemble(0x0602), ("LDA $10,X".to_owned(), 2));
+        assert_eq!(cpu.disassemble(0x0604), ("LDA ($20),Y".to_owned(), 2));
+        assert_eq!(cpu.disassemble(0x0606), ("BNE $0600".to_owned(), 2));
+        assert_eq!(cpu.disassemble(0x0608), ("JMP ($0200)".to_owned(), 3));
+        assert_eq!(cpu.disassemble(0x060B), ("ASL A".to_owned(), 1));
+        assert_eq!(cpu.disassemble(0x060C), (".byte $02".to_owned(), 1));
+        assert_eq!(cpu.disassemble(0x060D), ("*LAX $20".to_owned(), 2));
+        assert_eq!(cpu.disassemble(0x060F), ("NOP".to_owned(), 1));
+
+        cpu.set_symbols([(0x0600, "start"), (0x0020, "ptr")].into_iter().collect());
+        assert_eq!(cpu.disassemble(0x0604), ("LDA (ptr),Y".to_owned(), 2));
+        assert_eq!(cpu.disassemble(0x0606), ("BNE start".to_owned(), 2));
+        assert_eq!(cpu.disassemble(0x060D), ("*LAX ptr".to_owned(), 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_and_run_keeps_brk_policy() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Halt);
+        cpu.mem_write_u16(IRQ_VECTOR, 0x0700);
+        cpu.mem_write(0x0700, 0xC8); // INY
+        cpu.mem_write(0x0701, 0x02); // (illegal), halts
+        cpu.load_and_run([0xE8, 0x00])?; // INX; BRK
+
+        assert_eq!((cpu.register_x, cpu.register_y), (1, 1));
+        assert_eq!(cpu.program_counter, 0x0702);
+        assert_eq!(cpu.brk_policy, BrkPolicy::Interrupt);
+
+        cpu.set_brk_policy(BrkPolicy::Halt);
+        cpu.load_and_run([0xE8, 0x00])?;
+        assert_eq!((cpu.register_x, cpu.register_y), (1, 0));
+        assert_eq!(cpu.program_counter, 0x0602);
+        Ok(())
+    }
+
+    #[test]
+    fn test_jsr_at_top_of_memory_wraps() {
+        let mut cpu = CPU::default();
+        cpu.mem_write(0xFFFF, 0x20); // JSR $0700, operand wraps to 0x0000
+        cpu.mem_write_u16(0x0000, 0x0700);
+        cpu.program_counter = 0xFFFF;
+        cpu.step();
+
+        assert_eq!(cpu.program_counter, 0x0700);
+        assert_eq!(cpu.stack_peek(0), Some(0x01));
+    }
+
+    #[test]
+    fn test_trace_on_bus_only_peeks() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.memory_mut().set_strict(true);
+        cpu.mem_write(0x0000, 0xAD); // LDA $2002
+        cpu.mem_write_u16(0x0001, 0x2002);
+        cpu.program_counter = 0x0000;
+
+        // No PPU yet, so the register peeks as the last byte on the bus.
+        assert!(cpu.trace().starts_with("0000  AD 02 20  LDA $2002 = 20"));
+        assert_eq!(cpu.disassemble(0x0000), ("LDA $2002".to_owned(), 3));
+        assert_eq!(cpu.next_pc(), 0x0003);
+        assert!(cpu.memory_mut().take_unmapped_accesses().is_empty());
+    }
+
+    #[test]
+    fn test_illegal_opcode_policy() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([0x02, 0xE8, 0x00])?; // (illegal); INX; BRK
+        cpu.reset();
+        cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Halt);
+        assert!(cpu.step().halted);
+
+        cpu.reset();
+        cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Nop);
+        cpu.run();
+        assert_eq!(cpu.register_x, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_set_gates_unofficial_opcodes() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.mem_write(0x0010, 0x42);
+        cpu.load([0xA7, 0x10])?; // *LAX $10
+        cpu.reset();
+        cpu.set_opcode_set(OpcodeSet::Official);
+        assert_eq!(
+            cpu.try_step(),
+            Err(UnknownOpcode {
+                code: 0xA7,
+                pc: Some(0x0600)
+            })
+        );
+
+        cpu.set_opcode_set(OpcodeSet::Full);
+        assert_eq!(cpu.step().executed().map(|op| op.code), Some(0xA7));
+        assert_eq!((cpu.register_a, cpu.register_x), (0x42, 0x42));
+        Ok(())
+    }
+
+    /// Runs one random program until it halts or `max_instructions` have executed.
+    fn run_random_program<M: Memory>(mut cpu: CPU<M>, program: &[u8], max_instructions: usize) {
+        cpu.set_brk_policy(BrkPolicy::Halt);
+        cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Nop);
+        if cpu.load(program).is_ok() {
+            cpu.reset();
+            for _ in 0..max_instructions {
+                if cpu.step().halted {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Random programs must only ever halt, never panic, on flat memory and on the NES bus. Set
+    /// `FUZZ_SEED` to replay a failure.
+    #[test]
+    fn test_execute_random_programs() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        const PROGRAMS: u64 = 2_000;
+        const MAX_INSTRUCTIONS: usize = 1_000;
+
+        let base_seed = std::env::var("FUZZ_SEED")
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or(0x6502);
+
+        for seed in base_seed..base_seed + PROGRAMS {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let program: Vec<u8> = (0..rng.gen_range(1..0x200)).map(|_| rng.gen()).collect();
+
+            let result = std::panic::catch_unwind(|| {
+                run_random_program(CPU::default(), &program, MAX_INSTRUCTIONS);
+            });
+            assert!(result.is_ok(), "random program panicked, FUZZ_SEED={seed}");
+            let result = std::panic::catch_unwind(|| {
+                run_random_program(CPU::new(Bus::default()), &program, MAX_INSTRUCTIONS);
+            });
+            assert!(
+                result.is_ok(),
+                "random program panicked on the bus, FUZZ_SEED={seed}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_until_test_complete_returns_status() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([
+            0xA9, 0x80, //       LDA #$80
+            0x8D, 0x00, 0x60, // STA $6000 ; running
+            0xA9, 0xDE, //       LDA #$DE
+            0x8D, 0x01, 0x60, // STA $6001
+            0xA9, 0xB0, //       LDA #$B0
+            0x8D, 0x02, 0x60, // STA $6002
+            0xA9, 0x61, //       LDA #$61
+            0x8D, 0x03, 0x60, // STA $6003
+            0xE8, //             INX
+            0xA9, 0x03, //       LDA #$03
+            0x8D, 0x00, 0x60, // STA $6000 ; result
+            0xE8, //             INX
+            0x00, //             BRK
+        ])?;
+        cpu.reset();
+
+        let result = cpu.run_until_test_complete(0x6000, &[0xDE, 0xB0, 0x61], 100);
+        assert_eq!(result, Some(0x03));
+        assert_eq!(cpu.register_x, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_0xba_tsx_reads_stack_pointer() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load_and_run([0xA9, 0x01, 0x48, 0xBA, 0x00])?; // LDA #$01; PHA; TSX
+        assert_eq!(cpu.register_x, STACK_RESET - 1);
+        assert!(!cpu.status.contains(CpuFlags::ZERO));
+        assert!(cpu.status.contains(CpuFlags::NEGATIV));
+        Ok(())
+    }
+
+    #[test]
+    fn test_0x9a_txs_keeps_flags() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load_and_run([0xA2, 0x7F, 0xA9, 0x00, 0x9A, 0x00])?; // LDX #$7F; LDA #$00; TXS
+        assert_eq!(cpu.stack_ptr, 0x7F);
+        assert!(cpu.status.contains(CpuFlags::ZERO));
+        assert!(!cpu.status.contains(CpuFlags::NEGATIV));
+        Ok(())
+    }
+
+    #[test]
+    fn test_0x66_ror_zero_page_rotates_through_carry() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.mem_write(0x10, 0x03);
+        cpu.load_and_run([0x38, 0x66, 0x10, 0x00])?; // SEC; ROR $10
+        assert_eq!(cpu.mem_read(0x10), 0x81);
+        assert!(cpu.status.contains(CpuFlags::CARRY));
+        assert!(cpu.status.contains(CpuFlags::NEGATIV));
+
+        cpu.mem_write(0x10, 0x02);
+        cpu.load_and_run([0x18, 0x66, 0x10, 0x00])?; // CLC; ROR $10
+        assert_eq!(cpu.mem_read(0x10), 0x01);
+        assert!(!cpu.status.contains(CpuFlags::CARRY));
+        Ok(())
+    }
+
+    #[test]
+    fn test_0xd8_cld_clears_decimal() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load_and_run([0xF8, 0xD8, 0x00])?; // SED; CLD
+        assert!(!cpu.status.contains(CpuFlags::DECIMAL_MODE));
+        Ok(())
+    }
+
+    #[test]
+    fn test_0x58_cli_clears_interrupt_disable() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load_and_run([0x78, 0x58, 0x00])?; // SEI; CLI
+        assert!(!cpu.status.contains(CpuFlags::INTERUPT_DISABLE));
+        Ok(())
+    }
+
+    #[test]
+    fn test_0xb8_clv_clears_overflow() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([0xA9, 0x7F, 0x69, 0x01, 0xB8, 0x00])?; // LDA #$7F; ADC #$01; CLV
+        cpu.reset();
+        cpu.step();
+        cpu.step();
+        assert!(cpu.status.contains(CpuFlags::OVERFLOW));
+
+        cpu.run();
+        assert!(!cpu.status.contains(CpuFlags::OVERFLOW));
+        Ok(())
+    }
+
+    #[test]
+    fn test_try_step_reports_unknown_opcode() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([0xE8, 0x02, 0x00])?; // INX; (illegal); BRK
+        cpu.reset();
+
+        assert_eq!(
+            cpu.try_step().map(|step| step.executed().map(|op| op.code)),
+            Ok(Some(0xE8))
+        );
+        assert_eq!(
+            cpu.try_step(),
+            Err(UnknownOpcode {
+                code: 0x02,
+                pc: Some(0x0601)
+            })
+        );
+        assert_eq!(cpu.program_counter, 0x0601);
+        Ok(())
+    }
+
+    #[test]
+    fn test_try_run_stops_on_unknown_opcode() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        cpu.load([0xE8, 0xE8, 0x02, 0xE8, 0x00])?; // INX; INX; (illegal); INX; BRK
+        cpu.reset();
+
+        assert_eq!(
+            cpu.try_run(),
+            Err(UnknownOpcode {
+                code: 0x02,
+                pc: Some(0x0602)
+            })
+        );
+        assert_eq!((cpu.program_counter, cpu.register_x), (0x0602, 2));
+
+        cpu.program_counter = 0x0603;
+        assert_eq!(cpu.try_run(), Ok(()));
+        assert_eq!(cpu.register_x, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cpu_on_bus_mirrors_ram() -> Result<(), LoadError> {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.set_brk_policy(BrkPolicy::Halt);
+        cpu.load_and_run([0xA9, 0x42, 0x8D, 0x00, 0x00, 0x00])?; // LDA #$42; STA $0000; BRK
+
+        assert_eq!(cpu.mem_read(0x0800), 0x42);
+        assert_eq!(cpu.memory().mem_read(0x1800), 0x42);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cycles_sum_executed_opcodes() -> Result<(), LoadError> {
+        let mut cpu = test_cpu();
+        // LDA #$05 (2); TAX (2); INX (2); STA $10 (3); BRK (7)
+        cpu.load_and_run([0xA9, 0x05, 0xAA, 0xE8, 0x85, 0x10, 0x00])?;
+        assert_eq!(cpu.cycles(), 16);
+        Ok(())
+    }
+
+    #[test]
+    fn test_absolute_x_read_page_cross_costs_a_cycle() -> Result<(), LoadError> {
+        // LDX #$01; LDA $2010,X
+        assert_eq!(cycles_of_second_step([0xA2, 0x01, 0xBD, 0x10, 0x20])?, 4);
+        // LDX #$01; LDA $20FF,X
+        assert_eq!(cycles_of_second_step([0xA2, 0x01, 0xBD, 0xFF, 0x20])?, 5);
+        // LDX #$01; STA $20FF,X
+        assert_eq!(cycles_of_second_step([0xA2, 0x01, 0x9D, 0xFF, 0x20])?, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_branch_cycle_penalties() -> Result<(), LoadError> {
+        // LDX #$00; BNE (not taken)
+        assert_eq!(cycles_of_second_step([0xA2, 0x00, 0xD0, 0x02])?, 2);
+        // LDX #$01; BNE $0606
+        assert_eq!(cycles_of_second_step([0xA2, 0x01, 0xD0, 0x02])?, 3);
+        // LDX #$01; BNE $05F4
+        assert_eq!(cycles_of_second_step([0xA2, 0x01, 0xD0, 0xF0])?, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nmi_stacks_pc_and_status() {
+        let mut cpu = CPU::default();
+        cpu.power_on();
+        cpu.mem_write_u16(NMI_VECTOR, 0x0700);
+        cpu.program_counter = 0x1234;
+        cpu.status = CpuFlags::CARRY | CpuFlags::BREAK;
+
+        cpu.nmi();
+
+        assert_eq!(cpu.program_counter, 0x0700);
+        assert_eq!(cpu.cycles(), 7);
+        assert!(cpu.status.contains(CpuFlags::INTERUPT_DISABLE));
+        assert_eq!(cpu.stack_ptr, STACK_RESET - 3);
+        let status = CpuFlags::CARRY | CpuFlags::BREAK2;
+        assert_eq!(cpu.stack_peek(0), Some(status.bits()));
+        assert_eq!(cpu.stack_peek(1), Some(0x34));
+        assert_eq!(cpu.stack_peek(2), Some(0x12));
+    }
+
+    #[test]
+    fn test_requested_nmi_is_taken_before_next_instruction() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([0xE8, 0xE8, 0x00])?; // INX; INX; BRK
+        cpu.mem_write_u16(NMI_VECTOR, 0x0700);
+        cpu.mem_write(0x0700, 0xC8); // INY
+        cpu.reset();
+
+        cpu.step();
+        cpu.request_nmi();
+        cpu.step(); // NMI entry, then the handler's INY
+        assert_eq!(cpu.program_counter, 0x0701);
+        assert_eq!((cpu.register_x, cpu.register_y), (1, 1));
+        assert_eq!(cpu.stack_peek(1), Some(0x01));
+        Ok(())
+    }
+
+    #[test]
+    fn test_nmi_wins_over_due_irq() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([0xE8, 0xE8, 0x00])?; // INX; INX; BRK
+        cpu.mem_write_u16(NMI_VECTOR, 0x0700);
+        cpu.mem_write(0x0700, 0xC8); // INY
+        cpu.mem_write(0x0701, 0x40); // RTI
+        cpu.mem_write_u16(IRQ_VECTOR, 0x0800);
+        cpu.mem_write(0x0800, 0xEA); // NOP
+        cpu.power_on();
+        cpu.status.remove(CpuFlags::INTERUPT_DISABLE);
+
+        cpu.set_irq_line(true);
+        cpu.step(); // INX, polls the IRQ as due
+        cpu.request_nmi();
+        cpu.step(); // NMI entry, then the handler's INY
+
+        assert_eq!(cpu.program_counter, 0x0701);
+        assert_eq!(cpu.register_y, 1);
+        assert_eq!(cpu.stack_ptr, STACK_RESET - 3);
+
+        cpu.step(); // RTI back to the second INX, unmasking the still-asserted IRQ
+        assert_eq!(cpu.program_counter, 0x0601);
+        assert_eq!(cpu.stack_ptr, STACK_RESET);
+        cpu.step(); // IRQ entry, then the handler's NOP
+
+        assert_eq!(cpu.program_counter, 0x0801);
+        assert_eq!(cpu.register_x, 1);
+        assert_eq!(cpu.stack_ptr, STACK_RESET - 3);
+        assert_eq!(cpu.stack_peek(1), Some(0x01));
+        assert_eq!(cpu.stack_peek(2), Some(0x06));
+        Ok(())
+    }
+
+    #[test]
+    fn test_irq_taken_when_unmasked() {
+        let mut cpu = CPU::default();
+        cpu.power_on();
+        cpu.mem_write_u16(IRQ_VECTOR, 0x0700);
+        cpu.program_counter = 0x1234;
+        cpu.status.remove(CpuFlags::INTERUPT_DISABLE);
+
+        cpu.irq();
+
+        assert_eq!(cpu.program_counter, 0x0700);
+        assert_eq!(cpu.cycles(), 7);
+        assert!(cpu.status.contains(CpuFlags::INTERUPT_DISABLE));
+        let pushed = CpuFlags::from_bits_truncate(cpu.stack_peek(0).unwrap_or_default());
+        assert!(!pushed.contains(CpuFlags::BREAK));
+        assert!(pushed.contains(CpuFlags::BREAK2));
+        assert_eq!(cpu.stack_peek(1), Some(0x34));
+        assert_eq!(cpu.stack_peek(2), Some(0x12));
+    }
+
+    #[test]
+    fn test_irq_ignored_when_masked() {
+        let mut cpu = CPU::default();
+        cpu.power_on();
+        cpu.mem_write_u16(IRQ_VECTOR, 0x0700);
+        cpu.program_counter = 0x1234;
+        cpu.status.insert(CpuFlags::INTERUPT_DISABLE);
+
+        cpu.irq();
+
+        assert_eq!(cpu.program_counter, 0x1234);
+        assert_eq!(cpu.cycles(), 0);
+        assert_eq!(cpu.stack_ptr, STACK_RESET);
+    }
+
+    #[test]
+    fn test_brk_enters_irq_handler() -> Result<(), LoadError> {
+        let mut cpu = CPU::default();
+        cpu.load([0xE8, 0x00, 0xFF, 0xE8])?; // INX; BRK; (padding); INX
+        cpu.mem_write_u16(IRQ_VECTOR, 0x0700);
+        cpu.mem_write(0x0700, 0x40); // RTI
+        cpu.reset();
+        cpu.status.remove(CpuFlags::BREAK);
+
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x0700);
+        assert!(cpu.status.contains(CpuFlags::INTERUPT_DISABLE));
+        let stacked = CpuFlags::from_bits_truncate(cpu.stack_peek(0).unwrap_or_default());
+        assert!(stacked.contains(CpuFlags::BREAK | CpuFlags::BREAK2));
+        assert_eq!(cpu.stack_peek(1), Some(0x03));
+        assert_eq!(cpu.stack_peek(2), Some(0x06));
+
+        cpu.step(); // RTI skips the padding byte
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x0604);
+        assert_eq!(cpu.register_x, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_and_run_rejects_reset_vector_outside_program() {
+        let mut cpu = CPU::default();
+        assert_eq!(
+            cpu.load_and_run([]),
+            Err(LoadError::ResetVectorOutOfRange { vector: 0x0600 })
+        );
+    }
+
+    #[test]
+    fn test_nmi_vector_read() {
+        let mut cpu = CPU::default();
+        cpu.mem_write(0xFFFA, 0x34);
+        cpu.mem_write(0xFFFB, 0x12);
+
+        assert_eq!(cpu.mem_read_u16(NMI_VECTOR), 0x1234);
+    }
+
+    #[test]
+    fn test_irq_vector_read() {
+        let mut cpu = CPU::default();
+        cpu.mem_write(0xFFFE, 0x78);
+        cpu.mem_write(0xFFFF, 0x56);
+
+        assert_eq!(cpu.mem_read(0xFFFF), 0x56);
+        assert_eq!(cpu.mem_read_u16(IRQ_VECTOR), 0x5678);
+    }
+
+    #[test]
+    fn test_u16_read_wraps_at_top_of_memory() {
+        let mut cpu = CPU::default();
+        cpu.mem_write(0xFFFF, 0xCD);
+        cpu.mem_write(0x0000, 0xAB);
+
+        assert_eq!(cpu.mem_read_u16(0xFFFF), 0xABCD);
+    }
 }
@@ -0,0 +1,39 @@
+use super::{memory::Memory, CPU};
+
+/// Subroutine boundary observed by a call hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallEvent {
+    /// JSR at `caller` jumped to `target`.
+    Call { caller: u16, target: u16 },
+    /// RTS resumed execution at `target`.
+    Return { target: u16 },
+}
+
+/// Boxed callback fired on every JSR/RTS, see [`CPU::set_call_hook`].
+pub struct CallHook(Box<dyn FnMut(CallEvent)>);
+
+impl std::fmt::Debug for CallHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CallHook")
+    }
+}
+
+impl<M: Memory> CPU<M> {
+    /// Install a callback fired on each JSR and RTS, enough to rebuild the call tree of a run.
+    pub fn set_call_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(CallEvent) + 'static,
+    {
+        self.call_hook = Some(CallHook(Box::new(hook)));
+    }
+
+    pub fn clear_call_hook(&mut self) {
+        self.call_hook = None;
+    }
+
+    pub(super) fn fire_call_hook(&mut self, event: CallEvent) {
+        if let Some(CallHook(hook)) = &mut self.call_hook {
+            hook(event);
+        }
+    }
+}
@@ -39,3 +39,34 @@ bitflags! {
         const NEGATIV           = 0b1000_0000;
     }
 }
+
+impl CpuFlags {
+    /// Render the register as `NV-BDIZC`, uppercase for set flags and lowercase for clear ones.
+    ///
+    /// Bits 5 and 4 (BREAK2 and BREAK) don't exist as storage in the real register, they only
+    /// show up on the copy pushed to the stack, so both are always rendered as `-`.
+    #[must_use]
+    pub fn as_status_string(self) -> String {
+        const LETTERS: [(CpuFlags, char); 8] = [
+            (CpuFlags::NEGATIV, 'n'),
+            (CpuFlags::OVERFLOW, 'v'),
+            (CpuFlags::BREAK2, '-'),
+            (CpuFlags::BREAK, '-'),
+            (CpuFlags::DECIMAL_MODE, 'd'),
+            (CpuFlags::INTERUPT_DISABLE, 'i'),
+            (CpuFlags::ZERO, 'z'),
+            (CpuFlags::CARRY, 'c'),
+        ];
+
+        LETTERS
+            .iter()
+            .map(|&(flag, letter)| {
+                if self.contains(flag) {
+                    letter.to_ascii_uppercase()
+                } else {
+                    letter
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,67 @@
+use super::{cpuflags::CpuFlags, memory::Memory, ArithmeticCarry, CPU};
+
+const NIBBLE: u8 = 0x0F;
+
+impl<M: Memory> CPU<M> {
+    /// ADC with both operands as packed BCD, used while `DECIMAL_MODE` is set.
+    ///
+    /// Zero and negative follow the BCD result, overflow follows the binary sum.
+    pub(super) fn add_decimal(&mut self, data: u8) {
+        let a = self.register_a;
+        let carry_in = self.status.contains(CpuFlags::CARRY);
+
+        let mut lo = (a & NIBBLE) + (data & NIBBLE) + u8::from(carry_in);
+        let mut hi = (a >> 4) + (data >> 4);
+        if lo > 9 {
+            lo += 6;
+            hi += 1;
+        }
+        if hi > 9 {
+            hi += 6;
+        }
+
+        let binary = a.wrapping_add(data).wrapping_add(u8::from(carry_in));
+        self.status.set(
+            CpuFlags::OVERFLOW,
+            (binary ^ a) & (binary ^ data) & 0x80 != 0,
+        );
+        self.set_decimal_result(carry_in, hi > NIBBLE, hi << 4 | lo & NIBBLE);
+    }
+
+    /// SBC with both operands as packed BCD, used while `DECIMAL_MODE` is set.
+    ///
+    /// Zero and negative follow the BCD result, overflow follows the binary difference.
+    pub(super) fn subtract_decimal(&mut self, data: u8) {
+        let a = self.register_a;
+        let carry_in = self.status.contains(CpuFlags::CARRY);
+        let borrow = u8::from(!carry_in);
+
+        // A nibble that went below zero has bit 4 set; wrap it back into 0..=9.
+        let mut lo = (a & NIBBLE)
+            .wrapping_sub(data & NIBBLE)
+            .wrapping_sub(borrow);
+        let mut hi = (a >> 4).wrapping_sub(data >> 4);
+        if lo & 0x10 != 0 {
+            lo = lo.wrapping_sub(6);
+            hi = hi.wrapping_sub(1);
+        }
+        if hi & 0x10 != 0 {
+            hi = hi.wrapping_sub(6);
+        }
+
+        let binary = a.wrapping_sub(data).wrapping_sub(borrow);
+        self.status
+            .set(CpuFlags::OVERFLOW, (a ^ data) & (a ^ binary) & 0x80 != 0);
+        let carry_out = u16::from(a) >= u16::from(data) + u16::from(borrow);
+        self.set_decimal_result(carry_in, carry_out, hi << 4 | lo & NIBBLE);
+    }
+
+    fn set_decimal_result(&mut self, carry_in: bool, carry_out: bool, result: u8) {
+        self.status.set(CpuFlags::CARRY, carry_out);
+        self.last_carry = Some(ArithmeticCarry {
+            carry_in,
+            carry_out,
+        });
+        self.set_accumulator(result);
+    }
+}
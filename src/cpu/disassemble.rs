@@ -0,0 +1,70 @@
+use super::{memory::Memory, opcode_array, CPU};
+use crate::{addressing_mode::AddressingMode, opcode::OpCode};
+
+impl<M: Memory> CPU<M> {
+    /// The instruction at `addr` in assembler syntax, e.g. `LDA $10,X`, and its length in bytes.
+    ///
+    /// Addresses with a name in [`CPU::symbols`] are shown by name, and unofficial opcodes get a
+    /// `*` as in [`CPU::trace`]. A byte outside the opcode table comes back as `.byte $XX` with
+    /// length 1. Memory is only [peeked](Memory::peek).
+    #[must_use]
+    pub fn disassemble(&self, addr: u16) -> (String, u16) {
+        let code = self.peek(addr);
+        let Some(op) = opcode_array::lookup(code) else {
+            return (format!(".byte ${code:02X}"), 1);
+        };
+
+        let text = self.operand_syntax(op.mode, addr, |target, hex| self.symbol_or(target, hex));
+        let asm = if text.is_empty() {
+            listing_name(op)
+        } else {
+            format!("{} {text}", listing_name(op))
+        };
+        (asm, op.len.into())
+    }
+
+    /// The operand of the instruction at `addr` in assembler syntax, shared by [`CPU::trace`] and
+    /// [`CPU::disassemble`]. Every address goes through `name` along with its hex spelling, so the
+    /// disassembler can put a label in its place.
+    pub(super) fn operand_syntax(
+        &self,
+        mode: AddressingMode,
+        addr: u16,
+        name: impl Fn(u16, String) -> String,
+    ) -> String {
+        let operand = addr.wrapping_add(1);
+        let byte = self.peek(operand);
+        let word = self.peek_u16(operand);
+        let zero_page = || name(u16::from(byte), format!("${byte:02X}"));
+        let absolute = |target: u16| name(target, format!("${target:04X}"));
+
+        match mode {
+            AddressingMode::Implicit => String::new(),
+            AddressingMode::Accumulator => "A".to_owned(),
+            AddressingMode::Immediate => format!("#${byte:02X}"),
+            AddressingMode::ZeroPage => zero_page(),
+            AddressingMode::ZeroPage_X => format!("{},X", zero_page()),
+            AddressingMode::ZeroPage_Y => format!("{},Y", zero_page()),
+            AddressingMode::Absolute => absolute(word),
+            AddressingMode::Absolute_X => format!("{},X", absolute(word)),
+            AddressingMode::Absolute_Y => format!("{},Y", absolute(word)),
+            AddressingMode::Indirect => format!("({})", absolute(word)),
+            AddressingMode::Indirect_X => format!("({},X)", zero_page()),
+            AddressingMode::Indirect_Y => format!("({}),Y", zero_page()),
+            AddressingMode::Relative => {
+                let offset = i16::from(i8::from_le_bytes([byte]));
+                absolute(addr.wrapping_add(2).wrapping_add_signed(offset))
+            }
+        }
+    }
+
+    fn symbol_or(&self, addr: u16, hex: String) -> String {
+        self.symbols.get(addr).map_or(hex, str::to_owned)
+    }
+}
+
+/// The mnemonic as listings print it, with nestest's `*` in front of an unofficial opcode.
+pub(super) fn listing_name(op: OpCode) -> String {
+    let star = if op.official { "" } else { "*" };
+    format!("{star}{}", op.mnemonic)
+}
@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+
+use super::{cpuflags::CpuFlags, memory::Memory, CPU};
+
+/// CPU state from just before one instruction, plus the memory it overwrote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Snapshot {
+    register_a: u8,
+    register_x: u8,
+    register_y: u8,
+    status: CpuFlags,
+    program_counter: u16,
+    stack_ptr: u8,
+    cycles: u64,
+    nmi_pending: bool,
+    irq_due: bool,
+    /// `(addr, previous value)` in write order, undone in reverse.
+    writes: Vec<(u16, u8)>,
+}
+
+/// Ring buffer of the last `capacity` instructions, see [`CPU::record_history`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    capacity: usize,
+    snapshots: VecDeque<Snapshot>,
+    /// Set while a step runs, so writes from outside the CPU are not attributed to it.
+    recording: bool,
+}
+
+impl History {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            snapshots: VecDeque::with_capacity(capacity),
+            recording: false,
+        }
+    }
+}
+
+impl<M: Memory> CPU<M> {
+    /// Keep the state before each of the last `capacity` steps so they can be [`CPU::undo`]ne.
+    /// A capacity of 0 disables recording and drops the history.
+    pub fn record_history(&mut self, capacity: usize) {
+        self.history = (capacity > 0).then(|| History::new(capacity));
+    }
+
+    /// Number of steps [`CPU::undo`] can currently revert.
+    #[must_use]
+    pub fn undo_depth(&self) -> usize {
+        self.history
+            .as_ref()
+            .map_or(0, |history| history.snapshots.len())
+    }
+
+    /// Revert the most recent step: registers, flags, SP, PC, the cycle count, pending interrupts
+    /// and every byte it wrote to RAM. Writes to I/O registers stay done, see [`Memory::is_ram`].
+    ///
+    /// Returns `false` when there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(snapshot) = self
+            .history
+            .as_mut()
+            .and_then(|history| history.snapshots.pop_back())
+        else {
+            return false;
+        };
+
+        for &(addr, value) in snapshot.writes.iter().rev() {
+            self.memory.mem_write(addr, value);
+        }
+        self.register_a = snapshot.register_a;
+        self.register_x = snapshot.register_x;
+        self.register_y = snapshot.register_y;
+        self.status = snapshot.status;
+        self.program_counter = snapshot.program_counter;
+        self.stack_ptr = snapshot.stack_ptr;
+        self.cycles = snapshot.cycles;
+        self.nmi_pending = snapshot.nmi_pending;
+        self.irq_due = snapshot.irq_due;
+        true
+    }
+
+    pub(super) fn begin_history_entry(&mut self) {
+        let snapshot = Snapshot {
+            register_a: self.register_a,
+            register_x: self.register_x,
+            register_y: self.register_y,
+            status: self.status,
+            program_counter: self.program_counter,
+            stack_ptr: self.stack_ptr,
+            cycles: self.cycles,
+            nmi_pending: self.nmi_pending,
+            irq_due: self.irq_due,
+            writes: Vec::new(),
+        };
+
+        if let Some(history) = &mut self.history {
+            if history.snapshots.len() == history.capacity {
+                history.snapshots.pop_front();
+            }
+            history.snapshots.push_back(snapshot);
+            history.recording = true;
+        }
+    }
+
+    pub(super) const fn end_history_entry(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.recording = false;
+        }
+    }
+
+    /// Remember the byte at `addr` before the running step overwrites it.
+    pub(super) fn record_history_write(&mut self, addr: u16) {
+        if !self.memory.is_ram(addr) {
+            return;
+        }
+        if let Some(history) = self.history.as_mut().filter(|history| history.recording) {
+            let previous = self.memory.peek(addr);
+            if let Some(snapshot) = history.snapshots.back_mut() {
+                snapshot.writes.push((addr, previous));
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use super::{memory::Memory, CPU, MEMORY_SIZE};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// One bit per address, set once the address has been written (or loaded).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitMap {
+    bits: Vec<u64>,
+}
+
+impl Default for InitMap {
+    fn default() -> Self {
+        Self {
+            bits: vec![0; MEMORY_SIZE / WORD_BITS],
+        }
+    }
+}
+
+impl InitMap {
+    fn mark(&mut self, addr: usize) {
+        self.bits[addr / WORD_BITS] |= 1 << (addr % WORD_BITS);
+    }
+
+    fn is_initialized(&self, addr: usize) -> bool {
+        self.bits[addr / WORD_BITS] & (1 << (addr % WORD_BITS)) != 0
+    }
+}
+
+impl<M: Memory> CPU<M> {
+    /// Stop the run loop when an opcode is fetched from an address that was never written.
+    ///
+    /// Enable before `load` so the program bytes are recorded as initialized.
+    pub fn trap_uninitialized_fetch(&mut self, enabled: bool) {
+        self.init_map = enabled.then(InitMap::default);
+        self.uninitialized_fetch = None;
+    }
+
+    /// Address of the never-written opcode that stopped the last run, if the trap fired.
+    #[must_use]
+    pub const fn uninitialized_fetch(&self) -> Option<u16> {
+        self.uninitialized_fetch
+    }
+
+    pub(super) fn mark_initialized(&mut self, addrs: std::ops::Range<usize>) {
+        if let Some(map) = &mut self.init_map {
+            addrs.for_each(|addr| map.mark(addr));
+        }
+    }
+
+    /// `true` when the trap is enabled and `addr` was never written.
+    pub(super) fn is_uninitialized(&self, addr: u16) -> bool {
+        self.init_map
+            .as_ref()
+            .is_some_and(|map| !map.is_initialized(addr.into()))
+    }
+}
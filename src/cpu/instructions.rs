@@ -1,10 +1,18 @@
-use super::{AddressingMode, CpuFlags, Memory, CPU};
+use super::{call_graph::CallEvent, AddressingMode, CpuFlags, Memory, CPU, IRQ_VECTOR};
 
-impl CPU {
+impl<M: Memory> CPU<M> {
     /// ADC - Add with Carry
     #[allow(clippy::cast_possible_truncation)]
     pub(super) fn adc(&mut self, mode: AddressingMode) {
         let (_, data) = self.get_memory(mode);
+        self.adc_value(data);
+    }
+
+    fn adc_value(&mut self, data: u8) {
+        #[cfg(feature = "decimal")]
+        if self.status.contains(CpuFlags::DECIMAL_MODE) {
+            return self.add_decimal(data);
+        }
         self.add_to_accumulator(data);
     }
 
@@ -48,18 +56,18 @@ impl CPU {
     ///
     /// If the carry flag is clear then add the relative displacement to the program counter to
     /// cause a branch to a new location.
-    pub(super) fn bcc(&mut self) {
-        self.branch(!self.status.contains(CpuFlags::CARRY));
+    pub(super) fn bcc(&mut self) -> (bool, bool) {
+        self.branch(!self.status.contains(CpuFlags::CARRY))
     }
 
     /// BCS - Branch if Carry Set
-    pub(super) fn bcs(&mut self) {
-        self.branch(self.status.contains(CpuFlags::CARRY));
+    pub(super) fn bcs(&mut self) -> (bool, bool) {
+        self.branch(self.status.contains(CpuFlags::CARRY))
     }
 
     /// BEQ - Branch if Equal
-    pub(super) fn beq(&mut self) {
-        self.branch(self.status.contains(CpuFlags::ZERO));
+    pub(super) fn beq(&mut self) -> (bool, bool) {
+        self.branch(self.status.contains(CpuFlags::ZERO))
     }
 
     /// BIT - Bit Test
@@ -81,29 +89,39 @@ impl CPU {
     }
 
     /// BMI - Branch if Minus
-    pub(super) fn bmi(&mut self) {
-        self.branch(self.status.contains(CpuFlags::NEGATIV));
+    pub(super) fn bmi(&mut self) -> (bool, bool) {
+        self.branch(self.status.contains(CpuFlags::NEGATIV))
     }
 
     /// BNE - Branch if Not Equal
-    pub(super) fn bne(&mut self) {
-        self.branch(!self.status.contains(CpuFlags::ZERO));
+    pub(super) fn bne(&mut self) -> (bool, bool) {
+        self.branch(!self.status.contains(CpuFlags::ZERO))
     }
 
     /// BPL - Branch if Positive
-    pub(super) fn bpl(&mut self) {
-        self.branch(!self.status.contains(CpuFlags::NEGATIV));
+    pub(super) fn bpl(&mut self) -> (bool, bool) {
+        self.branch(!self.status.contains(CpuFlags::NEGATIV))
     }
 
     /// BRK - Force Interrupt
+    pub(super) fn brk(&mut self) {
+        // BRK skips the padding byte after it, so the handler returns past it.
+        self.stack_push_u16(self.program_counter.wrapping_add(1));
+        self.stack_push((self.status | CpuFlags::BREAK | CpuFlags::BREAK2).bits());
+
+        self.status.insert(CpuFlags::INTERUPT_DISABLE);
+        self.program_counter = self.mem_read_u16(IRQ_VECTOR);
+        self.stack_depth_enter();
+    }
+
     /// BVC - Branch if Overflow Clear
-    pub(super) fn bvc(&mut self) {
-        self.branch(!self.status.contains(CpuFlags::OVERFLOW));
+    pub(super) fn bvc(&mut self) -> (bool, bool) {
+        self.branch(!self.status.contains(CpuFlags::OVERFLOW))
     }
 
     /// BVS - Branch if Overflow Set
-    pub(super) fn bvs(&mut self) {
-        self.branch(self.status.contains(CpuFlags::OVERFLOW));
+    pub(super) fn bvs(&mut self) -> (bool, bool) {
+        self.branch(self.status.contains(CpuFlags::OVERFLOW))
     }
 
     /// CLC - Clear Carry Flag
@@ -178,20 +196,7 @@ impl CPU {
         }
         if mode == AddressingMode::Indirect {
             let addr = self.mem_read_u16(self.program_counter);
-            // An original 6502 has does not correctly fetch the target address if the indirect
-            // vector falls on a page boundary (e.g. $xxFF where xx is any value from $00 to $FF).
-            let is_page_boundary = addr & 0x00FF == 0x00FF;
-            let indirect_addr = if is_page_boundary {
-                // In this case fetches the LSB from $xxFF as expected
-                let low = self.mem_read(addr);
-                // but takes the MSB from $xx00.
-                let high = self.mem_read(addr & 0xFF00);
-                u16::from_le_bytes([low, high])
-            } else {
-                self.mem_read_u16(addr)
-            };
-
-            self.program_counter = indirect_addr;
+            self.program_counter = jmp_indirect_target(addr, |addr| self.mem_read(addr));
         }
     }
 
@@ -199,9 +204,16 @@ impl CPU {
     /// The JSR instruction pushes the address (minus one) of the return point on to the stack and
     /// then sets the program counter to the target memory address.
     pub(super) fn jsr(&mut self) {
-        self.stack_push_u16(self.program_counter + 2 - 1);
+        let caller = self.program_counter.wrapping_sub(1);
+        // program_counter sits on the operand, so the last byte of JSR is one further along
+        self.stack_push_u16(self.program_counter.wrapping_add(1));
         let target_addr = self.mem_read_u16(self.program_counter);
         self.program_counter = target_addr;
+        self.stack_depth_enter();
+        self.fire_call_hook(CallEvent::Call {
+            caller,
+            target: target_addr,
+        });
     }
     /// LDA - Load Accumulator
     pub(super) fn lda(&mut self, mode: AddressingMode) {
@@ -253,7 +265,14 @@ impl CPU {
     }
 
     /// NOP - No Operation
-    pub(super) const fn nop() {}
+    ///
+    /// The unofficial forms with an operand still perform the read.
+    pub(super) fn nop(&self, mode: AddressingMode) {
+        if mode != AddressingMode::Implicit {
+            let _ = self.get_memory(mode);
+        }
+    }
+
     /// ORA - Logical Inclusive OR
     pub(super) fn ora(&mut self, mode: AddressingMode) {
         let (addr, data) = self.get_memory(mode);
@@ -267,18 +286,22 @@ impl CPU {
 
     /// PHP - Push Processor Status
     pub(super) fn php(&mut self, mode: AddressingMode) {
-        self.stack_push(self.status.bits());
+        self.stack_push((self.status | CpuFlags::BREAK | CpuFlags::BREAK2).bits());
     }
 
     /// PLA - Pull Accumulator
     pub(super) fn pla(&mut self, mode: AddressingMode) {
+        self.stack_pull_dummy_reads();
         self.register_a = self.stack_pop();
         self.update_zero_and_negative_flags(self.register_a);
     }
 
     /// PLP - Pull Processor Status
     pub(super) fn plp(&mut self, mode: AddressingMode) {
+        self.stack_pull_dummy_reads();
         self.status = CpuFlags::from_bits_truncate(self.stack_pop());
+        self.status.remove(CpuFlags::BREAK);
+        self.status.insert(CpuFlags::BREAK2);
     }
 
     /// ROL - Rotate Left
@@ -286,7 +309,7 @@ impl CPU {
         if mode == AddressingMode::Accumulator {
             self.rol_accumulator();
         } else {
-            self.rol_memory(mode);
+            let _ = self.rol_memory(mode);
         }
     }
     fn rol_accumulator(&mut self) {
@@ -301,7 +324,7 @@ impl CPU {
         self.set_accumulator(data);
     }
 
-    fn rol_memory(&mut self, mode: AddressingMode) {
+    fn rol_memory(&mut self, mode: AddressingMode) -> u8 {
         let (addr, mut data) = self.get_memory(mode);
         let carry = self.status.contains(CpuFlags::CARRY);
         self.msb_to_carry_flag(data);
@@ -311,14 +334,15 @@ impl CPU {
         }
 
         self.set_memory(addr, data);
+        data
     }
 
     /// ROR - Rotate Right
     pub(super) fn ror(&mut self, mode: AddressingMode) {
         if mode == AddressingMode::Accumulator {
-            self.rol_accumulator();
+            self.ror_accumulator();
         } else {
-            self.rol_memory(mode);
+            let _ = self.ror_memory(mode);
         }
     }
     fn ror_accumulator(&mut self) {
@@ -333,7 +357,7 @@ impl CPU {
         self.set_accumulator(data);
     }
 
-    fn ror_memory(&mut self, mode: AddressingMode) {
+    fn ror_memory(&mut self, mode: AddressingMode) -> u8 {
         let (addr, mut data) = self.get_memory(mode);
         let carry = self.status.contains(CpuFlags::CARRY);
         self.lsb_to_carry_flag(data);
@@ -343,26 +367,46 @@ impl CPU {
         }
 
         self.set_memory(addr, data);
+        data
     }
 
     /// RTI - Return from Interrupt
     pub(super) fn rti(&mut self) {
+        self.stack_pull_dummy_reads();
         self.status = CpuFlags::from_bits_truncate(self.stack_pop());
         self.status.remove(CpuFlags::BREAK);
         self.status.insert(CpuFlags::BREAK2);
 
         self.program_counter = self.stack_pop_u16();
+        self.stack_depth_leave();
     }
 
     /// RTS - Return from Subroutine
     pub(super) fn rts(&mut self) {
-        self.program_counter = self.stack_pop_u16() + 1;
+        self.stack_pull_dummy_reads();
+        let return_addr = self.stack_pop_u16();
+        // last cycle reads the pulled address before incrementing past it
+        let _ = self.mem_read(return_addr);
+        self.program_counter = return_addr.wrapping_add(1);
+        self.stack_depth_leave();
+        self.fire_call_hook(CallEvent::Return {
+            target: self.program_counter,
+        });
     }
 
     /// SBC - Subtract with Carry
     #[allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
     pub(super) fn sbc(&mut self, mode: AddressingMode) {
         let (_, data) = self.get_memory(mode);
+        self.sbc_value(data);
+    }
+
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
+    fn sbc_value(&mut self, data: u8) {
+        #[cfg(feature = "decimal")]
+        if self.status.contains(CpuFlags::DECIMAL_MODE) {
+            return self.subtract_decimal(data);
+        }
         let data = i8::from_le_bytes([data]);
         let data = (data).wrapping_neg().wrapping_sub(1);
         let [data] = i8::to_le_bytes(data);
@@ -415,16 +459,98 @@ impl CPU {
     }
 
     /// TSX - Transfer Stack Pointer to X
+    pub(super) fn tsx(&mut self) {
+        self.register_x = self.stack_ptr;
+        self.update_zero_and_negative_flags(self.register_x);
+    }
+
     /// TXA - Transfer X to Accumulator
     pub(super) fn txa(&mut self) {
         self.register_a = self.register_x;
         self.update_zero_and_negative_flags(self.register_a);
     }
 
-    /// TXS - Transfer X to Stack Pointer
+    /// TXS - Transfer X to Stack Pointer, the one transfer that leaves the flags alone
+    pub(super) const fn txs(&mut self) {
+        self.stack_ptr = self.register_x;
+    }
+
     /// TYA - Transfer Y to Accumulator
     pub(super) fn tya(&mut self) {
         self.register_a = self.register_y;
         self.update_zero_and_negative_flags(self.register_a);
     }
+
+    // Unofficial opcodes
+
+    /// *DCP - Decrement Memory then Compare
+    pub(super) fn dcp(&mut self, mode: AddressingMode) {
+        let (addr, data) = self.get_memory(mode);
+        let data = data.wrapping_sub(1);
+        self.mem_write(addr, data);
+
+        self.status.set(CpuFlags::CARRY, self.register_a >= data);
+        self.update_zero_and_negative_flags(self.register_a.wrapping_sub(data));
+    }
+
+    /// *ISB - Increment Memory then Subtract with Carry
+    pub(super) fn isb(&mut self, mode: AddressingMode) {
+        let (addr, data) = self.get_memory(mode);
+        let data = data.wrapping_add(1);
+        self.mem_write(addr, data);
+        self.sbc_value(data);
+    }
+
+    /// *LAX - Load Accumulator and X Register
+    pub(super) fn lax(&mut self, mode: AddressingMode) {
+        let (addr, data) = self.get_memory(mode);
+        self.register_x = data;
+        self.set_accumulator(data);
+    }
+
+    /// *RLA - Rotate Left then AND
+    pub(super) fn rla(&mut self, mode: AddressingMode) {
+        let data = self.rol_memory(mode);
+        self.set_accumulator(self.register_a & data);
+    }
+
+    /// *RRA - Rotate Right then Add with Carry
+    pub(super) fn rra(&mut self, mode: AddressingMode) {
+        let data = self.ror_memory(mode);
+        self.adc_value(data);
+    }
+
+    /// *SAX - Store Accumulator AND X Register, flags unaffected
+    pub(super) fn sax(&mut self, mode: AddressingMode) {
+        let addr = self.get_operand_address(mode);
+        self.mem_write(addr, self.register_a & self.register_x);
+    }
+
+    /// *SLO - Shift Left then OR
+    pub(super) fn slo(&mut self, mode: AddressingMode) {
+        let data = self.asl_addr(mode);
+        self.set_accumulator(self.register_a | data);
+    }
+
+    /// *SRE - Shift Right then Exclusive OR
+    pub(super) fn sre(&mut self, mode: AddressingMode) {
+        let data = self.lsr_addr(mode);
+        self.set_accumulator(self.register_a ^ data);
+    }
+}
+
+/// Target of `JMP (addr)`, reading the vector through `read`.
+pub(super) fn jmp_indirect_target(addr: u16, read: impl Fn(u16) -> u8) -> u16 {
+    // An original 6502 has does not correctly fetch the target address if the indirect
+    // vector falls on a page boundary (e.g. $xxFF where xx is any value from $00 to $FF).
+    let is_page_boundary = addr & 0x00FF == 0x00FF;
+    // In this case fetches the LSB from $xxFF as expected
+    let low = read(addr);
+    // but takes the MSB from $xx00.
+    let high = if is_page_boundary {
+        read(addr & 0xFF00)
+    } else {
+        read(addr.wrapping_add(1))
+    };
+    u16::from_le_bytes([low, high])
 }
@@ -0,0 +1,72 @@
+use super::{cpuflags::CpuFlags, memory::Memory, Mnemonic, CPU, IRQ_VECTOR, NMI_VECTOR};
+
+const INTERRUPT_CYCLES: u64 = 7;
+
+impl<M: Memory> CPU<M> {
+    /// Take a non-maskable interrupt right away, jumping through the 0xFFFA vector.
+    pub fn nmi(&mut self) {
+        self.interrupt(NMI_VECTOR);
+    }
+
+    /// Latch an NMI edge (e.g. the PPU entering vblank); it is taken before the next instruction.
+    pub const fn request_nmi(&mut self) {
+        self.nmi_pending = true;
+    }
+
+    /// Take a latched NMI, checked by the run loop between instructions.
+    pub(super) fn poll_nmi(&mut self) {
+        if std::mem::take(&mut self.nmi_pending) {
+            self.nmi();
+            // NMI entry just set I, so the IRQ waits for the next poll, which will see it masked.
+            self.irq_due = false;
+        }
+    }
+
+    /// Take a maskable interrupt right away through the 0xFFFE vector, unless the I flag is set.
+    pub fn irq(&mut self) {
+        if !self.status.contains(CpuFlags::INTERUPT_DISABLE) {
+            self.interrupt(IRQ_VECTOR);
+        }
+    }
+
+    /// Drive the (level-triggered, active while `true`) IRQ line, e.g. from a mapper or the APU.
+    pub const fn set_irq_line(&mut self, asserted: bool) {
+        self.irq_line = asserted;
+    }
+
+    /// Decide whether the IRQ line is serviced before the next instruction.
+    ///
+    /// The 6502 polls near the end of an instruction, which is before CLI, SEI and PLP have
+    /// changed the I flag: their effect on interrupts is delayed by one instruction. RTI restores
+    /// the flag early enough to count immediately.
+    pub(super) const fn poll_irq(&mut self, mnemonic: Mnemonic, disabled_before: bool) {
+        let disabled = match mnemonic {
+            Mnemonic::Cli | Mnemonic::Sei | Mnemonic::Plp => disabled_before,
+            _ => self.status.contains(CpuFlags::INTERUPT_DISABLE),
+        };
+        self.irq_due = self.irq_line && !disabled;
+    }
+
+    /// Service an IRQ found by the last poll, before the next instruction is fetched.
+    pub(super) fn service_due_irq(&mut self) {
+        if std::mem::take(&mut self.irq_due) {
+            // The poll already checked the I flag as it stood at the end of the last instruction.
+            self.interrupt(IRQ_VECTOR);
+        }
+    }
+
+    /// Hardware interrupt sequence: stack PC and status (BREAK clear), mask IRQs, jump through
+    /// `vector`.
+    pub(super) fn interrupt(&mut self, vector: u16) {
+        self.stack_push_u16(self.program_counter);
+        let mut status = self.status;
+        status.remove(CpuFlags::BREAK);
+        status.insert(CpuFlags::BREAK2);
+        self.stack_push(status.bits());
+
+        self.status.insert(CpuFlags::INTERUPT_DISABLE);
+        self.program_counter = self.mem_read_u16(vector);
+        self.cycles += INTERRUPT_CYCLES;
+        self.stack_depth_enter();
+    }
+}
@@ -1,17 +1,85 @@
+use super::{MEMORY_SIZE, PRG_ROM_EXEC_ADDR};
+
 pub trait Memory {
     fn mem_read(&self, addr: u16) -> u8;
 
     fn mem_write(&mut self, addr: u16, data: u8);
 
+    /// Read without side effects, for tracing and debuggers. Memory whose reads change nothing
+    /// can keep the default.
+    fn peek(&self, addr: u16) -> u8 {
+        self.mem_read(addr)
+    }
+
+    /// Whether `addr` is plain storage, where writing back an old value undoes a write. I/O
+    /// registers are not: writing them again repeats their side effects.
+    fn is_ram(&self, addr: u16) -> bool {
+        true
+    }
+
+    fn peek_u16(&self, pos: u16) -> u16 {
+        u16::from_le_bytes([self.peek(pos), self.peek(pos.wrapping_add(1))])
+    }
+
+    fn peek_u16_zp_wrap(&self, pos: u8) -> u16 {
+        u16::from_le_bytes([self.peek(pos.into()), self.peek(pos.wrapping_add(1).into())])
+    }
+
     fn mem_read_u16(&self, pos: u16) -> u16 {
         let lo = self.mem_read(pos);
-        let hi = self.mem_read(pos + 1);
+        let hi = self.mem_read(pos.wrapping_add(1));
         u16::from_le_bytes([lo, hi])
     }
 
+    /// Zero-page pointer read: the high byte wraps to 0x00 when `pos` is 0xFF.
+    fn mem_read_u16_zp_wrap(&self, pos: u8) -> u16 {
+        let lo = self.mem_read(pos.into());
+        let hi = self.mem_read(pos.wrapping_add(1).into());
+        u16::from_le_bytes([lo, hi])
+    }
+
+    fn mem_write_u16_zp_wrap(&mut self, pos: u8, data: u16) {
+        let [lo, hi] = data.to_le_bytes();
+        self.mem_write(pos.into(), lo);
+        self.mem_write(pos.wrapping_add(1).into(), hi);
+    }
+
     fn mem_write_u16(&mut self, pos: u16, data: u16) {
         let [lo, hi] = data.to_le_bytes();
         self.mem_write(pos, lo);
-        self.mem_write(pos + 1, hi);
+        self.mem_write(pos.wrapping_add(1), hi);
+    }
+
+    /// Point the reset vector at 0xFFFC to `addr`. Memory that maps ROM there has to keep the
+    /// vector some other way.
+    fn set_reset_vector(&mut self, addr: u16) {
+        self.mem_write_u16(PRG_ROM_EXEC_ADDR, addr);
+    }
+}
+
+/// Plain 64 KiB of RAM: no mirroring, no memory-mapped I/O.
+///
+/// Useful for running arbitrary 6502 code where every address should behave like RAM, as opposed
+/// to the NES `Bus` which mirrors RAM and routes 0x2000.. to the PPU and friends.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FlatMemory {
+    data: Box<[u8]>,
+}
+
+impl Default for FlatMemory {
+    fn default() -> Self {
+        Self {
+            data: vec![0; MEMORY_SIZE].into_boxed_slice(),
+        }
+    }
+}
+
+impl Memory for FlatMemory {
+    fn mem_read(&self, addr: u16) -> u8 {
+        self.data[usize::from(addr)]
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.data[usize::from(addr)] = data;
     }
 }
@@ -1,14 +1,27 @@
 use crate::{
     addressing_mode::AddressingMode,
-    opcode::{self, mnemonic::Mnemonic, OpCode},
+    opcode::{self, mnemonic::Mnemonic, OpCode, UnknownOpcode},
 };
 
 #[must_use]
 pub fn decode(raw: opcode::Raw) -> OpCode {
-    INSTRUCTIONS[usize::from(raw)].unwrap_or_else(|| panic!("OpCode {raw:#04x} is not recognized."))
+    try_decode(raw).unwrap_or_else(|err| panic!("{err}"))
 }
 
-const LEN: usize = 0xFF;
+pub fn try_decode(raw: opcode::Raw) -> Result<OpCode, UnknownOpcode> {
+    lookup(raw).ok_or(UnknownOpcode {
+        code: raw,
+        pc: None,
+    })
+}
+
+/// The opcode for `raw`, `None` for bytes outside the implemented set.
+#[must_use]
+pub fn lookup(raw: opcode::Raw) -> Option<OpCode> {
+    INSTRUCTIONS[usize::from(raw)]
+}
+
+const LEN: usize = 0x100;
 const INSTRUCTIONS: [Option<OpCode>; LEN] = padded_array();
 
 const fn padded_array() -> [Option<OpCode>; LEN] {
@@ -24,7 +37,7 @@ const fn padded_array() -> [Option<OpCode>; LEN] {
     array
 }
 
-const INSTRUCTION_ARRAY: [OpCode; 151] = [
+pub const INSTRUCTION_ARRAY: [OpCode; 231] = [
     // ADC - Add with Carry
     (OpCode::new(0x69, Mnemonic::Adc, 2, 2, AddressingMode::Immediate)),
     (OpCode::new(0x65, Mnemonic::Adc, 2, 3, AddressingMode::ZeroPage)),
@@ -44,7 +57,7 @@ const INSTRUCTION_ARRAY: [OpCode; 151] = [
     (OpCode::new(0x21, Mnemonic::And, 2, 6, AddressingMode::Indirect_X)),
     (OpCode::new(0x31, Mnemonic::And, 2, 5, AddressingMode::Indirect_Y)), /* +1 if page crossed */
     // ASL - Arithmetic Shift Left
-    (OpCode::new(0x0A, Mnemonic::Asl, 1, 2, AddressingMode::Implicit)),
+    (OpCode::new(0x0A, Mnemonic::Asl, 1, 2, AddressingMode::Accumulator)),
     (OpCode::new(0x06, Mnemonic::Asl, 2, 5, AddressingMode::ZeroPage)),
     (OpCode::new(0x16, Mnemonic::Asl, 2, 6, AddressingMode::ZeroPage_X)),
     (OpCode::new(0x0E, Mnemonic::Asl, 3, 6, AddressingMode::Absolute)),
@@ -73,11 +86,11 @@ const INSTRUCTION_ARRAY: [OpCode; 151] = [
     // CLC - Clear Carry Flag
     (OpCode::new(0x18, Mnemonic::Clc, 1, 2, AddressingMode::Implicit)),
     // CLD - Clear Decimal Mode
-    (OpCode::new(0xD8, Mnemonic::Clc, 1, 2, AddressingMode::Implicit)),
+    (OpCode::new(0xD8, Mnemonic::Cld, 1, 2, AddressingMode::Implicit)),
     // CLI - Clear Interrupt Disable
-    (OpCode::new(0x58, Mnemonic::Clc, 1, 2, AddressingMode::Implicit)),
+    (OpCode::new(0x58, Mnemonic::Cli, 1, 2, AddressingMode::Implicit)),
     // CLV - Clear Overflow Flag
-    (OpCode::new(0xB8, Mnemonic::Clc, 1, 2, AddressingMode::Implicit)),
+    (OpCode::new(0xB8, Mnemonic::Clv, 1, 2, AddressingMode::Implicit)),
     // CMP - Compare
     (OpCode::new(0xC9, Mnemonic::Cmp, 2, 2, AddressingMode::Immediate)),
     (OpCode::new(0xC5, Mnemonic::Cmp, 2, 3, AddressingMode::ZeroPage)),
@@ -218,7 +231,7 @@ const INSTRUCTION_ARRAY: [OpCode; 151] = [
     (OpCode::new(0x8E, Mnemonic::Stx, 3, 4, AddressingMode::Absolute)),
     // STY - Store Y Register
     (OpCode::new(0x84, Mnemonic::Sty, 2, 3, AddressingMode::ZeroPage)),
-    (OpCode::new(0x94, Mnemonic::Sty, 2, 4, AddressingMode::ZeroPage_Y)),
+    (OpCode::new(0x94, Mnemonic::Sty, 2, 4, AddressingMode::ZeroPage_X)),
     (OpCode::new(0x8C, Mnemonic::Sty, 3, 4, AddressingMode::Absolute)),
     // TAX - Transfer Accumulator to X
     (OpCode::new(0xAA, Mnemonic::Tax, 1, 2, AddressingMode::Implicit)),
@@ -232,4 +245,130 @@ const INSTRUCTION_ARRAY: [OpCode; 151] = [
     (OpCode::new(0x9A, Mnemonic::Txs, 1, 2, AddressingMode::Implicit)),
     // TYA - Transfer Y to Accumulator
     (OpCode::new(0x98, Mnemonic::Tya, 1, 2, AddressingMode::Implicit)),
+    // Unofficial opcodes, <https://www.nesdev.org/undocumented_opcodes.txt>
+    // *DCP - Decrement Memory then Compare
+    (OpCode::unofficial(0xC7, Mnemonic::Dcp, 2, 5, AddressingMode::ZeroPage)),
+    (OpCode::unofficial(0xD7, Mnemonic::Dcp, 2, 6, AddressingMode::ZeroPage_X)),
+    (OpCode::unofficial(0xCF, Mnemonic::Dcp, 3, 6, AddressingMode::Absolute)),
+    (OpCode::unofficial(0xDF, Mnemonic::Dcp, 3, 7, AddressingMode::Absolute_X)),
+    (OpCode::unofficial(0xDB, Mnemonic::Dcp, 3, 7, AddressingMode::Absolute_Y)),
+    (OpCode::unofficial(0xC3, Mnemonic::Dcp, 2, 8, AddressingMode::Indirect_X)),
+    (OpCode::unofficial(0xD3, Mnemonic::Dcp, 2, 8, AddressingMode::Indirect_Y)),
+    // *ISB - Increment Memory then Subtract with Carry
+    (OpCode::unofficial(0xE7, Mnemonic::Isb, 2, 5, AddressingMode::ZeroPage)),
+    (OpCode::unofficial(0xF7, Mnemonic::Isb, 2, 6, AddressingMode::ZeroPage_X)),
+    (OpCode::unofficial(0xEF, Mnemonic::Isb, 3, 6, AddressingMode::Absolute)),
+    (OpCode::unofficial(0xFF, Mnemonic::Isb, 3, 7, AddressingMode::Absolute_X)),
+    (OpCode::unofficial(0xFB, Mnemonic::Isb, 3, 7, AddressingMode::Absolute_Y)),
+    (OpCode::unofficial(0xE3, Mnemonic::Isb, 2, 8, AddressingMode::Indirect_X)),
+    (OpCode::unofficial(0xF3, Mnemonic::Isb, 2, 8, AddressingMode::Indirect_Y)),
+    // *LAX - Load Accumulator and X Register
+    (OpCode::unofficial(0xA7, Mnemonic::Lax, 2, 3, AddressingMode::ZeroPage)),
+    (OpCode::unofficial(0xB7, Mnemonic::Lax, 2, 4, AddressingMode::ZeroPage_Y)),
+    (OpCode::unofficial(0xAF, Mnemonic::Lax, 3, 4, AddressingMode::Absolute)),
+    (OpCode::unofficial(0xBF, Mnemonic::Lax, 3, 4, AddressingMode::Absolute_Y)), /* +1 if page crossed */
+    (OpCode::unofficial(0xA3, Mnemonic::Lax, 2, 6, AddressingMode::Indirect_X)),
+    (OpCode::unofficial(0xB3, Mnemonic::Lax, 2, 5, AddressingMode::Indirect_Y)), /* +1 if page crossed */
+    // *NOP - No Operation
+    (OpCode::unofficial(0x1A, Mnemonic::Nop, 1, 2, AddressingMode::Implicit)),
+    (OpCode::unofficial(0x3A, Mnemonic::Nop, 1, 2, AddressingMode::Implicit)),
+    (OpCode::unofficial(0x5A, Mnemonic::Nop, 1, 2, AddressingMode::Implicit)),
+    (OpCode::unofficial(0x7A, Mnemonic::Nop, 1, 2, AddressingMode::Implicit)),
+    (OpCode::unofficial(0xDA, Mnemonic::Nop, 1, 2, AddressingMode::Implicit)),
+    (OpCode::unofficial(0xFA, Mnemonic::Nop, 1, 2, AddressingMode::Implicit)),
+    (OpCode::unofficial(0x80, Mnemonic::Nop, 2, 2, AddressingMode::Immediate)),
+    (OpCode::unofficial(0x82, Mnemonic::Nop, 2, 2, AddressingMode::Immediate)),
+    (OpCode::unofficial(0x89, Mnemonic::Nop, 2, 2, AddressingMode::Immediate)),
+    (OpCode::unofficial(0xC2, Mnemonic::Nop, 2, 2, AddressingMode::Immediate)),
+    (OpCode::unofficial(0xE2, Mnemonic::Nop, 2, 2, AddressingMode::Immediate)),
+    (OpCode::unofficial(0x04, Mnemonic::Nop, 2, 3, AddressingMode::ZeroPage)),
+    (OpCode::unofficial(0x44, Mnemonic::Nop, 2, 3, AddressingMode::ZeroPage)),
+    (OpCode::unofficial(0x64, Mnemonic::Nop, 2, 3, AddressingMode::ZeroPage)),
+    (OpCode::unofficial(0x14, Mnemonic::Nop, 2, 4, AddressingMode::ZeroPage_X)),
+    (OpCode::unofficial(0x34, Mnemonic::Nop, 2, 4, AddressingMode::ZeroPage_X)),
+    (OpCode::unofficial(0x54, Mnemonic::Nop, 2, 4, AddressingMode::ZeroPage_X)),
+    (OpCode::unofficial(0x74, Mnemonic::Nop, 2, 4, AddressingMode::ZeroPage_X)),
+    (OpCode::unofficial(0xD4, Mnemonic::Nop, 2, 4, AddressingMode::ZeroPage_X)),
+    (OpCode::unofficial(0xF4, Mnemonic::Nop, 2, 4, AddressingMode::ZeroPage_X)),
+    (OpCode::unofficial(0x0C, Mnemonic::Nop, 3, 4, AddressingMode::Absolute)),
+    (OpCode::unofficial(0x1C, Mnemonic::Nop, 3, 4, AddressingMode::Absolute_X)), /* +1 if page crossed */
+    (OpCode::unofficial(0x3C, Mnemonic::Nop, 3, 4, AddressingMode::Absolute_X)), /* +1 if page crossed */
+    (OpCode::unofficial(0x5C, Mnemonic::Nop, 3, 4, AddressingMode::Absolute_X)), /* +1 if page crossed */
+    (OpCode::unofficial(0x7C, Mnemonic::Nop, 3, 4, AddressingMode::Absolute_X)), /* +1 if page crossed */
+    (OpCode::unofficial(0xDC, Mnemonic::Nop, 3, 4, AddressingMode::Absolute_X)), /* +1 if page crossed */
+    (OpCode::unofficial(0xFC, Mnemonic::Nop, 3, 4, AddressingMode::Absolute_X)), /* +1 if page crossed */
+    // *RLA - Rotate Left then AND
+    (OpCode::unofficial(0x27, Mnemonic::Rla, 2, 5, AddressingMode::ZeroPage)),
+    (OpCode::unofficial(0x37, Mnemonic::Rla, 2, 6, AddressingMode::ZeroPage_X)),
+    (OpCode::unofficial(0x2F, Mnemonic::Rla, 3, 6, AddressingMode::Absolute)),
+    (OpCode::unofficial(0x3F, Mnemonic::Rla, 3, 7, AddressingMode::Absolute_X)),
+    (OpCode::unofficial(0x3B, Mnemonic::Rla, 3, 7, AddressingMode::Absolute_Y)),
+    (OpCode::unofficial(0x23, Mnemonic::Rla, 2, 8, AddressingMode::Indirect_X)),
+    (OpCode::unofficial(0x33, Mnemonic::Rla, 2, 8, AddressingMode::Indirect_Y)),
+    // *RRA - Rotate Right then Add with Carry
+    (OpCode::unofficial(0x67, Mnemonic::Rra, 2, 5, AddressingMode::ZeroPage)),
+    (OpCode::unofficial(0x77, Mnemonic::Rra, 2, 6, AddressingMode::ZeroPage_X)),
+    (OpCode::unofficial(0x6F, Mnemonic::Rra, 3, 6, AddressingMode::Absolute)),
+    (OpCode::unofficial(0x7F, Mnemonic::Rra, 3, 7, AddressingMode::Absolute_X)),
+    (OpCode::unofficial(0x7B, Mnemonic::Rra, 3, 7, AddressingMode::Absolute_Y)),
+    (OpCode::unofficial(0x63, Mnemonic::Rra, 2, 8, AddressingMode::Indirect_X)),
+    (OpCode::unofficial(0x73, Mnemonic::Rra, 2, 8, AddressingMode::Indirect_Y)),
+    // *SAX - Store Accumulator AND X Register
+    (OpCode::unofficial(0x87, Mnemonic::Sax, 2, 3, AddressingMode::ZeroPage)),
+    (OpCode::unofficial(0x97, Mnemonic::Sax, 2, 4, AddressingMode::ZeroPage_Y)),
+    (OpCode::unofficial(0x8F, Mnemonic::Sax, 3, 4, AddressingMode::Absolute)),
+    (OpCode::unofficial(0x83, Mnemonic::Sax, 2, 6, AddressingMode::Indirect_X)),
+    // *SBC - Subtract with Carry
+    (OpCode::unofficial(0xEB, Mnemonic::Sbc, 2, 2, AddressingMode::Immediate)),
+    // *SLO - Shift Left then OR
+    (OpCode::unofficial(0x07, Mnemonic::Slo, 2, 5, AddressingMode::ZeroPage)),
+    (OpCode::unofficial(0x17, Mnemonic::Slo, 2, 6, AddressingMode::ZeroPage_X)),
+    (OpCode::unofficial(0x0F, Mnemonic::Slo, 3, 6, AddressingMode::Absolute)),
+    (OpCode::unofficial(0x1F, Mnemonic::Slo, 3, 7, AddressingMode::Absolute_X)),
+    (OpCode::unofficial(0x1B, Mnemonic::Slo, 3, 7, AddressingMode::Absolute_Y)),
+    (OpCode::unofficial(0x03, Mnemonic::Slo, 2, 8, AddressingMode::Indirect_X)),
+    (OpCode::unofficial(0x13, Mnemonic::Slo, 2, 8, AddressingMode::Indirect_Y)),
+    // *SRE - Shift Right then Exclusive OR
+    (OpCode::unofficial(0x47, Mnemonic::Sre, 2, 5, AddressingMode::ZeroPage)),
+    (OpCode::unofficial(0x57, Mnemonic::Sre, 2, 6, AddressingMode::ZeroPage_X)),
+    (OpCode::unofficial(0x4F, Mnemonic::Sre, 3, 6, AddressingMode::Absolute)),
+    (OpCode::unofficial(0x5F, Mnemonic::Sre, 3, 7, AddressingMode::Absolute_X)),
+    (OpCode::unofficial(0x5B, Mnemonic::Sre, 3, 7, AddressingMode::Absolute_Y)),
+    (OpCode::unofficial(0x43, Mnemonic::Sre, 2, 8, AddressingMode::Indirect_X)),
+    (OpCode::unofficial(0x53, Mnemonic::Sre, 2, 8, AddressingMode::Indirect_Y)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_every_byte() {
+        for raw in 0x00..=0xFF {
+            let decoded = std::panic::catch_unwind(|| decode(raw));
+            match (lookup(raw), decoded) {
+                (Some(op), Ok(decoded)) => assert_eq!(op.code, decoded.code),
+                (None, Err(panic)) => {
+                    let message = panic.downcast_ref::<String>().map(String::as_str);
+                    assert_eq!(
+                        message,
+                        Some(format!("OpCode {raw:#04x} is not recognized.").as_str())
+                    );
+                }
+                (op, decoded) => panic!("{raw:#04x}: lookup {op:?}, decode {decoded:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_decode_unknown_byte() {
+        assert_eq!(
+            try_decode(0x02),
+            Err(UnknownOpcode {
+                code: 0x02,
+                pc: None
+            })
+        );
+        assert_eq!(try_decode(0xEA).map(|op| op.mnemonic), Ok(Mnemonic::Nop));
+    }
+}
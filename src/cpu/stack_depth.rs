@@ -0,0 +1,55 @@
+use super::{memory::Memory, CPU};
+
+/// Call-depth bookkeeping used when analysing subroutine nesting.
+///
+/// JSR and interrupt entry push a frame, RTS and RTI pop one. The depth never underflows, so a
+/// program that returns more often than it calls (e.g. one that manipulates the stack by hand)
+/// simply stays at zero.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StackDepth {
+    depth: usize,
+    max_depth: usize,
+}
+
+impl StackDepth {
+    fn enter(&mut self) {
+        self.depth += 1;
+        self.max_depth = self.max_depth.max(self.depth);
+    }
+
+    const fn leave(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+}
+
+impl<M: Memory> CPU<M> {
+    /// Enable or disable the call-depth tracker. Enabling starts from a clean count.
+    pub fn track_stack_depth(&mut self, enabled: bool) {
+        self.stack_depth = enabled.then(StackDepth::default);
+    }
+
+    /// Current call depth, `None` when tracking is disabled.
+    #[must_use]
+    pub fn stack_depth(&self) -> Option<usize> {
+        self.stack_depth.map(|tracker| tracker.depth)
+    }
+
+    /// Deepest call nesting reached since tracking was enabled (or the last reset), `None` when
+    /// tracking is disabled.
+    #[must_use]
+    pub fn max_stack_depth(&self) -> Option<usize> {
+        self.stack_depth.map(|tracker| tracker.max_depth)
+    }
+
+    pub(super) fn stack_depth_enter(&mut self) {
+        if let Some(tracker) = &mut self.stack_depth {
+            tracker.enter();
+        }
+    }
+
+    pub(super) const fn stack_depth_leave(&mut self) {
+        if let Some(tracker) = &mut self.stack_depth {
+            tracker.leave();
+        }
+    }
+}
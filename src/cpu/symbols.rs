@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+
+use super::{memory::Memory, CPU};
+
+/// Address to label map, e.g. loaded from an assembler's label listing.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SymbolTable {
+    names: BTreeMap<u16, String>,
+}
+
+impl SymbolTable {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name `addr`, replacing any earlier name for it.
+    pub fn insert(&mut self, addr: u16, name: impl Into<String>) {
+        self.names.insert(addr, name.into());
+    }
+
+    #[must_use]
+    pub fn get(&self, addr: u16) -> Option<&str> {
+        self.names.get(&addr).map(String::as_str)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+}
+
+impl<S: Into<String>> FromIterator<(u16, S)> for SymbolTable {
+    fn from_iter<I: IntoIterator<Item = (u16, S)>>(iter: I) -> Self {
+        Self {
+            names: iter
+                .into_iter()
+                .map(|(addr, name)| (addr, name.into()))
+                .collect(),
+        }
+    }
+}
+
+impl<M: Memory> CPU<M> {
+    /// Labels used in place of raw addresses when rendering instructions.
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = symbols;
+    }
+
+    #[must_use]
+    pub const fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+}
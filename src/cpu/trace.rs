@@ -0,0 +1,89 @@
+use super::{
+    disassemble::listing_name, instructions::jmp_indirect_target, memory::Memory, opcode_array, CPU,
+};
+use crate::{addressing_mode::AddressingMode, opcode::mnemonic::Mnemonic};
+use std::fmt::Write;
+
+/// Column the `A:` register dump starts at in nestest.log.
+const REGISTERS_COLUMN: usize = 48;
+
+impl<M: Memory> CPU<M> {
+    /// The instruction at the program counter as a nestest.log line, without the PPU and CYC
+    /// columns:
+    ///
+    /// `C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD`
+    ///
+    /// Memory operands show the effective address and the value there, as nestest does. Memory is
+    /// only [peeked](Memory::peek), nothing is executed.
+    #[must_use]
+    pub fn trace(&self) -> String {
+        let pc = self.program_counter;
+        let code = self.peek(pc);
+        let (name, len, operand) = opcode_array::lookup(code).map_or_else(
+            || ("???".to_owned(), 1, String::new()),
+            |op| {
+                let operand = self.trace_operand(op.mnemonic, op.mode, pc);
+                (listing_name(op), op.len, operand)
+            },
+        );
+
+        let mut bytes = String::new();
+        for addr in (pc..).take(len.into()) {
+            let _ = write!(bytes, "{:02X} ", self.peek(addr));
+        }
+
+        let asm = format!("{pc:04X}  {bytes:9}{name:>4} {operand}");
+        format!(
+            "{:width$} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+            asm.trim_end(),
+            self.register_a,
+            self.register_x,
+            self.register_y,
+            self.status.bits(),
+            self.stack_ptr,
+            width = REGISTERS_COLUMN - 1,
+        )
+    }
+
+    /// The operand as [`CPU::disassemble`] spells it, without labels, followed by the
+    /// effective address and value nestest annotates it with.
+    fn trace_operand(&self, mnemonic: Mnemonic, mode: AddressingMode, pc: u16) -> String {
+        let syntax = self.operand_syntax(mode, pc, |_, hex| hex);
+        let byte = self.peek(pc.wrapping_add(1));
+        let addr = || self.operand_address_at(mode, pc.wrapping_add(1), |addr| self.peek(addr));
+        let value = || self.peek(addr());
+
+        match mode {
+            AddressingMode::Implicit
+            | AddressingMode::Accumulator
+            | AddressingMode::Immediate
+            | AddressingMode::Relative => syntax,
+            AddressingMode::Absolute if matches!(mnemonic, Mnemonic::Jmp | Mnemonic::Jsr) => syntax,
+            AddressingMode::ZeroPage | AddressingMode::Absolute => {
+                format!("{syntax} = {:02X}", value())
+            }
+            AddressingMode::ZeroPage_X | AddressingMode::ZeroPage_Y => {
+                format!("{syntax} @ {:02X} = {:02X}", addr(), value())
+            }
+            AddressingMode::Absolute_X | AddressingMode::Absolute_Y => {
+                format!("{syntax} @ {:04X} = {:02X}", addr(), value())
+            }
+            AddressingMode::Indirect => format!(
+                "{syntax} = {:04X}",
+                jmp_indirect_target(self.peek_u16(pc.wrapping_add(1)), |addr| self.peek(addr))
+            ),
+            AddressingMode::Indirect_X => format!(
+                "{syntax} @ {:02X} = {:04X} = {:02X}",
+                byte.wrapping_add(self.register_x),
+                addr(),
+                value()
+            ),
+            AddressingMode::Indirect_Y => format!(
+                "{syntax} = {:04X} @ {:04X} = {:02X}",
+                self.peek_u16_zp_wrap(byte),
+                addr(),
+                value()
+            ),
+        }
+    }
+}
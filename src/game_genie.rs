@@ -0,0 +1,126 @@
+//! NES Game Genie codes.
+//!
+//! Each letter is a nibble and the nibbles' bits are scrambled into a PRG address, a replacement
+//! value and, for 8-letter codes, a compare value the original byte has to match.
+
+const LETTERS: [char; 16] = [
+    'A', 'P', 'Z', 'L', 'G', 'I', 'T', 'Y', 'E', 'O', 'X', 'U', 'K', 'S', 'V', 'N',
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GameGenie {
+    pub addr: u16,
+    pub value: u8,
+    /// Only patch when the original byte is this, 8-letter codes only.
+    pub compare: Option<u8>,
+}
+
+impl GameGenie {
+    /// The byte a read at `addr` returns when the unpatched byte there is `original`.
+    #[must_use]
+    pub fn patch(&self, addr: u16, original: u8) -> Option<u8> {
+        (addr == self.addr && self.compare.is_none_or(|compare| compare == original))
+            .then_some(self.value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseGameGenieError {
+    Length(usize),
+    Letter(char),
+}
+
+impl std::fmt::Display for ParseGameGenieError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Length(len) => write!(f, "Game Genie codes are 6 or 8 letters, got {len}"),
+            Self::Letter(letter) => write!(f, "'{letter}' is not a Game Genie letter"),
+        }
+    }
+}
+
+impl std::error::Error for ParseGameGenieError {}
+
+impl std::str::FromStr for GameGenie {
+    type Err = ParseGameGenieError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let n = s
+            .trim()
+            .chars()
+            .map(|letter| {
+                (0_u8..)
+                    .zip(LETTERS)
+                    .find_map(|(nibble, known)| {
+                        (known == letter.to_ascii_uppercase()).then_some(nibble)
+                    })
+                    .ok_or(ParseGameGenieError::Letter(letter))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if n.len() != 6 && n.len() != 8 {
+            return Err(ParseGameGenieError::Length(n.len()));
+        }
+
+        let bits = |index: usize, mask: u8| u16::from(n[index] & mask);
+        let addr = 0x8000
+            | (bits(3, 7) << 12)
+            | (bits(5, 7) << 8)
+            | (bits(4, 8) << 8)
+            | (bits(2, 7) << 4)
+            | (bits(1, 8) << 4)
+            | bits(4, 7)
+            | bits(3, 8);
+        // The bit 8-letter codes spend on the compare value comes from the last letter instead.
+        let value_low_bit = if n.len() == 8 { n[7] } else { n[5] } & 8;
+        let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7) | value_low_bit;
+        let compare =
+            (n.len() == 8).then(|| ((n[7] & 7) << 4) | ((n[6] & 8) << 4) | (n[6] & 7) | (n[5] & 8));
+
+        Ok(Self {
+            addr,
+            value,
+            compare,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_six_letter_code() {
+        assert_eq!(
+            "GOSSIP".parse(),
+            Ok(GameGenie {
+                addr: 0xD1DD,
+                value: 0x14,
+                compare: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_eight_letter_code() {
+        assert_eq!(
+            "ZEXPYGLA".parse(),
+            Ok(GameGenie {
+                addr: 0x94A7,
+                value: 0x02,
+                compare: Some(0x03)
+            })
+        );
+    }
+
+    #[test]
+    fn test_reject_bad_codes() {
+        assert_eq!(
+            "GOSSI".parse::<GameGenie>(),
+            Err(ParseGameGenieError::Length(5))
+        );
+        assert_eq!(
+            "GOSSIB".parse::<GameGenie>(),
+            Err(ParseGameGenieError::Letter('B'))
+        );
+    }
+}
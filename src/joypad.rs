@@ -0,0 +1,195 @@
+use std::{cell::Cell, collections::VecDeque};
+
+use bitflags::bitflags;
+
+bitflags! {
+    /// Buttons in the order the controller's shift register reports them.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct JoypadButton: u8 {
+        const BUTTON_A = 0b0000_0001;
+        const BUTTON_B = 0b0000_0010;
+        const SELECT   = 0b0000_0100;
+        const START    = 0b0000_1000;
+        const UP       = 0b0001_0000;
+        const DOWN     = 0b0010_0000;
+        const LEFT     = 0b0100_0000;
+        const RIGHT    = 0b1000_0000;
+    }
+}
+
+/// Where a controller's buttons come from, consulted once per [`Joypad::next_frame`].
+#[derive(Debug, Default)]
+pub enum InputSource {
+    /// Whatever [`Joypad::set_button`] last set.
+    #[default]
+    Live,
+    Replay(ReplayInput),
+}
+
+/// Pre-recorded button states, one per frame. Once it runs out every button reads released.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReplayInput {
+    frames: VecDeque<JoypadButton>,
+}
+
+impl ReplayInput {
+    pub fn new(frames: impl IntoIterator<Item = JoypadButton>) -> Self {
+        Self {
+            frames: frames.into_iter().collect(),
+        }
+    }
+
+    fn next_frame(&mut self) -> JoypadButton {
+        self.frames.pop_front().unwrap_or_default()
+    }
+}
+
+/// Standard controller: a strobe latch and an 8-bit shift register read one button at a time.
+#[derive(Debug, Default)]
+pub struct Joypad {
+    strobe: bool,
+    // A read shifts the register, but the bus reads through `&self`.
+    button_index: Cell<u8>,
+    buttons: JoypadButton,
+    source: InputSource,
+    /// Button state presented each frame, `Some` once [`Joypad::start_recording`] was called.
+    recording: Option<Vec<JoypadButton>>,
+}
+
+impl Joypad {
+    /// While the strobe bit is set the controller keeps reloading, so reads always report A.
+    pub fn write(&mut self, data: u8) {
+        self.strobe = data & 1 == 1;
+        if self.strobe {
+            self.button_index.set(0);
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        let data = self.peek();
+        let index = self.button_index.get();
+        if index <= 7 && !self.strobe {
+            self.button_index.set(index + 1);
+        }
+        data
+    }
+
+    /// The bit the next [`Joypad::read`] returns, without shifting.
+    #[must_use]
+    pub const fn peek(&self) -> u8 {
+        let index = self.button_index.get();
+        if index > 7 {
+            // An official controller shifts in 1s once all eight buttons were read.
+            return 1;
+        }
+        (self.buttons.bits() >> index) & 1
+    }
+
+    pub fn set_button(&mut self, button: JoypadButton, pressed: bool) {
+        self.buttons.set(button, pressed);
+    }
+
+    pub fn set_input_source(&mut self, source: InputSource) {
+        self.source = source;
+    }
+
+    /// Start of a frame: a replay presents its next button state, live input is left alone.
+    /// Whatever the frame ends up with is appended to a running recording.
+    pub fn next_frame(&mut self) {
+        if let InputSource::Replay(replay) = &mut self.source {
+            self.buttons = replay.next_frame();
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.push(self.buttons);
+        }
+    }
+
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// Frames recorded so far, oldest first. Recording carries on into a fresh `Vec`.
+    pub fn take_recording(&mut self) -> Vec<JoypadButton> {
+        self.recording
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    #[must_use]
+    pub const fn buttons(&self) -> JoypadButton {
+        self.buttons
+    }
+
+    #[must_use]
+    pub const fn strobe(&self) -> bool {
+        self.strobe
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_presents_one_state_per_frame() {
+        let frames = [
+            JoypadButton::BUTTON_A,
+            JoypadButton::UP | JoypadButton::BUTTON_B,
+            JoypadButton::START,
+        ];
+        let mut joypad = Joypad::default();
+        joypad.set_input_source(InputSource::Replay(ReplayInput::new(frames)));
+
+        for buttons in frames {
+            joypad.next_frame();
+            joypad.write(1);
+            joypad.write(0);
+            let reported = (0..8).fold(0, |bits, index| bits | (joypad.read() << index));
+            assert_eq!(reported, buttons.bits());
+        }
+        joypad.next_frame();
+        assert_eq!(joypad.buttons(), JoypadButton::empty());
+    }
+
+    #[test]
+    fn test_recording_captures_each_frame() {
+        let mut joypad = Joypad::default();
+        joypad.start_recording();
+        joypad.set_button(JoypadButton::LEFT, true);
+        joypad.next_frame();
+        joypad.set_button(JoypadButton::BUTTON_A, true);
+        joypad.next_frame();
+        joypad.set_button(JoypadButton::LEFT, false);
+        joypad.next_frame();
+
+        let recording = joypad.take_recording();
+        assert_eq!(
+            recording,
+            [
+                JoypadButton::LEFT,
+                JoypadButton::LEFT | JoypadButton::BUTTON_A,
+                JoypadButton::BUTTON_A
+            ]
+        );
+
+        // Played back, a recording reproduces the same frames.
+        let mut replayed = Joypad::default();
+        replayed.set_input_source(InputSource::Replay(ReplayInput::new(recording.clone())));
+        replayed.start_recording();
+        for _ in 0..recording.len() {
+            replayed.next_frame();
+        }
+        assert_eq!(replayed.take_recording(), recording);
+        assert!(replayed.take_recording().is_empty());
+    }
+
+    #[test]
+    fn test_live_input_survives_next_frame() {
+        let mut joypad = Joypad::default();
+        joypad.set_button(JoypadButton::SELECT, true);
+        joypad.next_frame();
+
+        assert_eq!(joypad.buttons(), JoypadButton::SELECT);
+    }
+}
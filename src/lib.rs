@@ -18,10 +18,16 @@
     clippy::multiple_crate_versions
 )]
 
-mod addressing_mode;
-mod bus;
+pub mod addressing_mode;
+pub mod apu;
+pub mod bus;
+pub mod cartridge;
+pub mod clock;
 pub mod cpu;
-mod opcode;
+pub mod game_genie;
+pub mod joypad;
+pub mod machine;
+pub mod opcode;
 
 /*
     Central Processing Unit (CPU)
@@ -0,0 +1,122 @@
+//! A CPU built from whatever bytes the user has: a bare 6502 program or an iNES image.
+
+use std::fmt;
+
+use crate::{
+    bus::Bus,
+    cartridge::{Rom, RomError, MAGIC},
+    cpu::{LoadError, CPU},
+};
+
+/// A CPU ready to run, on flat memory for a bare program or on the NES bus for a cartridge.
+#[derive(Debug)]
+pub enum Machine {
+    /// A raw program, loaded at 0x0600 with the reset vector pointing at it.
+    Flat(Box<CPU>),
+    /// An iNES image with its PRG-ROM mapped and the PC at the cartridge's reset vector.
+    Nes(Box<CPU<Bus>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineError {
+    /// The bytes start with the iNES magic but are not a usable cartridge.
+    Rom(RomError),
+    /// The bytes are a raw program that does not fit, see [`CPU::load`].
+    Load(LoadError),
+}
+
+impl fmt::Display for MachineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rom(err) => err.fmt(f),
+            Self::Load(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for MachineError {}
+
+impl From<RomError> for MachineError {
+    fn from(err: RomError) -> Self {
+        Self::Rom(err)
+    }
+}
+
+impl From<LoadError> for MachineError {
+    fn from(err: LoadError) -> Self {
+        Self::Load(err)
+    }
+}
+
+/// Sniffs the iNES magic: with it the bytes are parsed as a cartridge, without it they are a raw
+/// program.
+impl TryFrom<&[u8]> for Machine {
+    type Error = MachineError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.starts_with(&MAGIC) {
+            let mut cpu = CPU::new(Bus::new(Rom::from_ines(bytes)?));
+            cpu.reset();
+            Ok(Self::Nes(Box::new(cpu)))
+        } else {
+            let mut cpu = CPU::default();
+            cpu.load(bytes)?;
+            cpu.reset();
+            Ok(Self::Flat(Box::new(cpu)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cartridge::{CHR_ROM_PAGE_SIZE, PRG_ROM_PAGE_SIZE},
+        cpu::{memory::Memory, BrkPolicy},
+    };
+
+    #[test]
+    fn test_raw_program_runs_on_flat_memory() -> Result<(), MachineError> {
+        let program: &[u8] = &[0xA9, 0x05, 0x85, 0x10, 0x00]; // LDA #$05; STA $10; BRK
+        let Machine::Flat(mut cpu) = Machine::try_from(program)? else {
+            panic!("a raw program should not be taken for a cartridge");
+        };
+        cpu.set_brk_policy(BrkPolicy::Halt);
+        cpu.run();
+
+        assert_eq!(cpu.mem_read(0x0010), 0x05);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ines_image_runs_from_its_reset_vector() -> Result<(), MachineError> {
+        let mut image = MAGIC.to_vec();
+        image.extend([1, 1]);
+        image.resize(16, 0);
+        let mut prg_rom = vec![0; PRG_ROM_PAGE_SIZE];
+        // 0x8000: LDA #$07; STA $10; BRK
+        prg_rom[..5].copy_from_slice(&[0xA9, 0x07, 0x85, 0x10, 0x00]);
+        prg_rom[0x3FFC..0x3FFE].copy_from_slice(&[0x00, 0x80]);
+        image.extend(prg_rom);
+        image.extend([0; CHR_ROM_PAGE_SIZE]);
+
+        let Machine::Nes(mut cpu) = Machine::try_from(image.as_slice())? else {
+            panic!("an iNES image should get a cartridge bus");
+        };
+        cpu.set_brk_policy(BrkPolicy::Halt);
+        cpu.run();
+
+        assert_eq!(cpu.mem_read(0x0010), 0x07);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_ines_image_is_a_rom_error() {
+        let image = MAGIC.to_vec();
+
+        assert!(matches!(
+            Machine::try_from(image.as_slice()),
+            Err(MachineError::Rom(RomError::Truncated { .. }))
+        ));
+    }
+}
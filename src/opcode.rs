@@ -1,8 +1,9 @@
 use self::mnemonic::Mnemonic;
-use crate::addressing_mode::AddressingMode;
+use crate::{addressing_mode::AddressingMode, cpu::opcode_array::INSTRUCTION_ARRAY};
 use std::{borrow::Borrow, collections::HashSet, str::FromStr};
 
 pub mod mnemonic;
+pub mod reference;
 
 pub type Raw = u8;
 
@@ -13,6 +14,8 @@ pub struct OpCode {
     pub len: u8,
     pub cycles: u8,
     pub mode: AddressingMode,
+    /// `false` for the undocumented opcodes (LAX, DCP, the extra NOPs, ...).
+    pub official: bool,
 }
 
 impl PartialEq for OpCode {
@@ -33,7 +36,28 @@ impl Borrow<Raw> for OpCode {
     }
 }
 
+/// A byte that is not in the opcode table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownOpcode {
+    pub code: Raw,
+    /// Where it was fetched, `None` when decoded outside the CPU.
+    pub pc: Option<u16>,
+}
+
+impl std::fmt::Display for UnknownOpcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OpCode {:#04x} is not recognized.", self.code)?;
+        if let Some(pc) = self.pc {
+            write!(f, " (at {pc:#06x})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnknownOpcode {}
+
 impl OpCode {
+    #[must_use]
     pub const fn new(
         code: Raw,
         mnemonic: Mnemonic,
@@ -47,6 +71,42 @@ impl OpCode {
             len,
             cycles,
             mode: addr,
+            official: true,
         }
     }
+
+    /// An undocumented opcode, see [`OpCode::official`].
+    #[must_use]
+    pub const fn unofficial(
+        code: Raw,
+        mnemonic: Mnemonic,
+        len: u8,
+        cycles: u8,
+        addr: AddressingMode,
+    ) -> Self {
+        Self {
+            official: false,
+            ..Self::new(code, mnemonic, len, cycles, addr)
+        }
+    }
+
+    /// Every opcode the CPU implements, in table order.
+    #[must_use]
+    pub const fn table() -> &'static [Self] {
+        &INSTRUCTION_ARRAY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_has_every_opcode_once() {
+        let table = OpCode::table();
+        assert_eq!(table.len(), INSTRUCTION_ARRAY.len());
+
+        let codes: HashSet<Raw> = table.iter().map(|op| op.code).collect();
+        assert_eq!(codes.len(), table.len(), "duplicate opcode in table");
+    }
 }
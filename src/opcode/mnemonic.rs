@@ -56,4 +56,28 @@ pub enum Mnemonic {
     Txa,
     Txs,
     Tya,
+    // Unofficial
+    /// DEC then CMP
+    Dcp,
+    /// INC then SBC
+    Isb,
+    /// LDA and LDX from one read
+    Lax,
+    /// ROL then AND
+    Rla,
+    /// ROR then ADC
+    Rra,
+    /// Store A AND X
+    Sax,
+    /// ASL then ORA
+    Slo,
+    /// LSR then EOR
+    Sre,
+}
+
+impl std::fmt::Display for Mnemonic {
+    /// Assembler spelling, e.g. `LDA`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(&format!("{self:?}").to_uppercase())
+    }
 }
@@ -0,0 +1,135 @@
+//! The 6502 instruction set as published, kept apart from the CPU's own table so the
+//! implementation can be checked against it.
+//!
+//! <http://www.6502.org/tutorials/6502opcodes.html>
+//! <https://www.nesdev.org/undocumented_opcodes.txt>
+
+/// `(opcode, mnemonic, length in bytes, base cycles)` for the 151 official opcodes. Base cycles
+/// exclude the page-cross and branch-taken penalties.
+#[rustfmt::skip]
+pub const OFFICIAL_OPCODES: &[(u8, &str, u8, u8)] = &[
+    (0x69, "ADC", 2, 2), (0x65, "ADC", 2, 3), (0x75, "ADC", 2, 4), (0x6D, "ADC", 3, 4),
+    (0x7D, "ADC", 3, 4), (0x79, "ADC", 3, 4), (0x61, "ADC", 2, 6), (0x71, "ADC", 2, 5),
+    (0x29, "AND", 2, 2), (0x25, "AND", 2, 3), (0x35, "AND", 2, 4), (0x2D, "AND", 3, 4),
+    (0x3D, "AND", 3, 4), (0x39, "AND", 3, 4), (0x21, "AND", 2, 6), (0x31, "AND", 2, 5),
+    (0x0A, "ASL", 1, 2), (0x06, "ASL", 2, 5), (0x16, "ASL", 2, 6), (0x0E, "ASL", 3, 6),
+    (0x1E, "ASL", 3, 7),
+    (0x90, "BCC", 2, 2), (0xB0, "BCS", 2, 2), (0xF0, "BEQ", 2, 2), (0x30, "BMI", 2, 2),
+    (0xD0, "BNE", 2, 2), (0x10, "BPL", 2, 2), (0x50, "BVC", 2, 2), (0x70, "BVS", 2, 2),
+    (0x24, "BIT", 2, 3), (0x2C, "BIT", 3, 4),
+    (0x00, "BRK", 1, 7), (0x18, "CLC", 1, 2), (0xD8, "CLD", 1, 2), (0x58, "CLI", 1, 2),
+    (0xB8, "CLV", 1, 2),
+    (0xC9, "CMP", 2, 2), (0xC5, "CMP", 2, 3), (0xD5, "CMP", 2, 4), (0xCD, "CMP", 3, 4),
+    (0xDD, "CMP", 3, 4), (0xD9, "CMP", 3, 4), (0xC1, "CMP", 2, 6), (0xD1, "CMP", 2, 5),
+    (0xE0, "CPX", 2, 2), (0xE4, "CPX", 2, 3), (0xEC, "CPX", 3, 4),
+    (0xC0, "CPY", 2, 2), (0xC4, "CPY", 2, 3), (0xCC, "CPY", 3, 4),
+    (0xC6, "DEC", 2, 5), (0xD6, "DEC", 2, 6), (0xCE, "DEC", 3, 6), (0xDE, "DEC", 3, 7),
+    (0xCA, "DEX", 1, 2), (0x88, "DEY", 1, 2),
+    (0x49, "EOR", 2, 2), (0x45, "EOR", 2, 3), (0x55, "EOR", 2, 4), (0x4D, "EOR", 3, 4),
+    (0x5D, "EOR", 3, 4), (0x59, "EOR", 3, 4), (0x41, "EOR", 2, 6), (0x51, "EOR", 2, 5),
+    (0xE6, "INC", 2, 5), (0xF6, "INC", 2, 6), (0xEE, "INC", 3, 6), (0xFE, "INC", 3, 7),
+    (0xE8, "INX", 1, 2), (0xC8, "INY", 1, 2),
+    (0x4C, "JMP", 3, 3), (0x6C, "JMP", 3, 5),
+    (0x20, "JSR", 3, 6),
+    (0xA9, "LDA", 2, 2), (0xA5, "LDA", 2, 3), (0xB5, "LDA", 2, 4), (0xAD, "LDA", 3, 4),
+    (0xBD, "LDA", 3, 4), (0xB9, "LDA", 3, 4), (0xA1, "LDA", 2, 6), (0xB1, "LDA", 2, 5),
+    (0xA2, "LDX", 2, 2), (0xA6, "LDX", 2, 3), (0xB6, "LDX", 2, 4), (0xAE, "LDX", 3, 4),
+    (0xBE, "LDX", 3, 4),
+    (0xA0, "LDY", 2, 2), (0xA4, "LDY", 2, 3), (0xB4, "LDY", 2, 4), (0xAC, "LDY", 3, 4),
+    (0xBC, "LDY", 3, 4),
+    (0x4A, "LSR", 1, 2), (0x46, "LSR", 2, 5), (0x56, "LSR", 2, 6), (0x4E, "LSR", 3, 6),
+    (0x5E, "LSR", 3, 7),
+    (0xEA, "NOP", 1, 2),
+    (0x09, "ORA", 2, 2), (0x05, "ORA", 2, 3), (0x15, "ORA", 2, 4), (0x0D, "ORA", 3, 4),
+    (0x1D, "ORA", 3, 4), (0x19, "ORA", 3, 4), (0x01, "ORA", 2, 6), (0x11, "ORA", 2, 5),
+    (0x48, "PHA", 1, 3), (0x08, "PHP", 1, 3), (0x68, "PLA", 1, 4), (0x28, "PLP", 1, 4),
+    (0x2A, "ROL", 1, 2), (0x26, "ROL", 2, 5), (0x36, "ROL", 2, 6), (0x2E, "ROL", 3, 6),
+    (0x3E, "ROL", 3, 7),
+    (0x6A, "ROR", 1, 2), (0x66, "ROR", 2, 5), (0x76, "ROR", 2, 6), (0x6E, "ROR", 3, 6),
+    (0x7E, "ROR", 3, 7),
+    (0x40, "RTI", 1, 6), (0x60, "RTS", 1, 6),
+    (0xE9, "SBC", 2, 2), (0xE5, "SBC", 2, 3), (0xF5, "SBC", 2, 4), (0xED, "SBC", 3, 4),
+    (0xFD, "SBC", 3, 4), (0xF9, "SBC", 3, 4), (0xE1, "SBC", 2, 6), (0xF1, "SBC", 2, 5),
+    (0x38, "SEC", 1, 2), (0xF8, "SED", 1, 2), (0x78, "SEI", 1, 2),
+    (0x85, "STA", 2, 3), (0x95, "STA", 2, 4), (0x8D, "STA", 3, 4), (0x9D, "STA", 3, 5),
+    (0x99, "STA", 3, 5), (0x81, "STA", 2, 6), (0x91, "STA", 2, 6),
+    (0x86, "STX", 2, 3), (0x96, "STX", 2, 4), (0x8E, "STX", 3, 4),
+    (0x84, "STY", 2, 3), (0x94, "STY", 2, 4), (0x8C, "STY", 3, 4),
+    (0xAA, "TAX", 1, 2), (0xA8, "TAY", 1, 2), (0xBA, "TSX", 1, 2), (0x8A, "TXA", 1, 2),
+    (0x9A, "TXS", 1, 2), (0x98, "TYA", 1, 2),
+];
+
+/// `(opcode, mnemonic, length in bytes, base cycles)` for the unofficial opcodes nestest
+/// exercises, with the same cycle convention as [`OFFICIAL_OPCODES`].
+#[rustfmt::skip]
+pub const UNOFFICIAL_OPCODES: &[(u8, &str, u8, u8)] = &[
+    (0xC7, "DCP", 2, 5), (0xD7, "DCP", 2, 6), (0xCF, "DCP", 3, 6), (0xDF, "DCP", 3, 7),
+    (0xDB, "DCP", 3, 7), (0xC3, "DCP", 2, 8), (0xD3, "DCP", 2, 8),
+    (0xE7, "ISB", 2, 5), (0xF7, "ISB", 2, 6), (0xEF, "ISB", 3, 6), (0xFF, "ISB", 3, 7),
+    (0xFB, "ISB", 3, 7), (0xE3, "ISB", 2, 8), (0xF3, "ISB", 2, 8),
+    (0xA7, "LAX", 2, 3), (0xB7, "LAX", 2, 4), (0xAF, "LAX", 3, 4), (0xBF, "LAX", 3, 4),
+    (0xA3, "LAX", 2, 6), (0xB3, "LAX", 2, 5),
+    (0x1A, "NOP", 1, 2), (0x3A, "NOP", 1, 2), (0x5A, "NOP", 1, 2), (0x7A, "NOP", 1, 2),
+    (0xDA, "NOP", 1, 2), (0xFA, "NOP", 1, 2),
+    (0x80, "NOP", 2, 2), (0x82, "NOP", 2, 2), (0x89, "NOP", 2, 2), (0xC2, "NOP", 2, 2),
+    (0xE2, "NOP", 2, 2),
+    (0x04, "NOP", 2, 3), (0x44, "NOP", 2, 3), (0x64, "NOP", 2, 3),
+    (0x14, "NOP", 2, 4), (0x34, "NOP", 2, 4), (0x54, "NOP", 2, 4), (0x74, "NOP", 2, 4),
+    (0xD4, "NOP", 2, 4), (0xF4, "NOP", 2, 4),
+    (0x0C, "NOP", 3, 4),
+    (0x1C, "NOP", 3, 4), (0x3C, "NOP", 3, 4), (0x5C, "NOP", 3, 4), (0x7C, "NOP", 3, 4),
+    (0xDC, "NOP", 3, 4), (0xFC, "NOP", 3, 4),
+    (0x27, "RLA", 2, 5), (0x37, "RLA", 2, 6), (0x2F, "RLA", 3, 6), (0x3F, "RLA", 3, 7),
+    (0x3B, "RLA", 3, 7), (0x23, "RLA", 2, 8), (0x33, "RLA", 2, 8),
+    (0x67, "RRA", 2, 5), (0x77, "RRA", 2, 6), (0x6F, "RRA", 3, 6), (0x7F, "RRA", 3, 7),
+    (0x7B, "RRA", 3, 7), (0x63, "RRA", 2, 8), (0x73, "RRA", 2, 8),
+    (0x87, "SAX", 2, 3), (0x97, "SAX", 2, 4), (0x8F, "SAX", 3, 4), (0x83, "SAX", 2, 6),
+    (0xEB, "SBC", 2, 2),
+    (0x07, "SLO", 2, 5), (0x17, "SLO", 2, 6), (0x0F, "SLO", 3, 6), (0x1F, "SLO", 3, 7),
+    (0x1B, "SLO", 3, 7), (0x03, "SLO", 2, 8), (0x13, "SLO", 2, 8),
+    (0x47, "SRE", 2, 5), (0x57, "SRE", 2, 6), (0x4F, "SRE", 3, 6), (0x5F, "SRE", 3, 7),
+    (0x5B, "SRE", 3, 7), (0x43, "SRE", 2, 8), (0x53, "SRE", 2, 8),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opcode::OpCode;
+
+    #[test]
+    fn test_instruction_table_matches_reference() {
+        let table = OpCode::table();
+        let reference = || {
+            let official = OFFICIAL_OPCODES.iter().map(|&row| (row, true));
+            official.chain(UNOFFICIAL_OPCODES.iter().map(|&row| (row, false)))
+        };
+        let mut mismatches: Vec<String> = reference()
+            .filter_map(|((code, name, len, cycles), official)| {
+                let Some(op) = table.iter().find(|op| op.code == code) else {
+                    return Some(format!("{code:#04x}: {name} missing"));
+                };
+                let actual = (
+                    format!("{:?}", op.mnemonic).to_uppercase(),
+                    op.len,
+                    op.cycles,
+                    op.official,
+                );
+                (actual != (name.to_owned(), len, cycles, official)).then(|| {
+                    format!(
+                        "{code:#04x}: expected {name} {len}b {cycles}c official={official}, \
+                         got {} {}b {}c official={}",
+                        actual.0, actual.1, actual.2, actual.3
+                    )
+                })
+            })
+            .collect();
+        mismatches.extend(
+            table
+                .iter()
+                .filter(|op| reference().all(|((code, ..), _)| code != op.code))
+                .map(|op| format!("{:#04x}: not in the reference tables", op.code)),
+        );
+
+        assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+    }
+}